@@ -1,3 +1,6 @@
 fn main() {
-    ded::run().expect("Could not run");
+    if let Err(e) = ded::run() {
+        eprintln!("ded: {}", e);
+        std::process::exit(1);
+    }
 }
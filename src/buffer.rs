@@ -0,0 +1,290 @@
+use std::{io, ops::Range};
+use ropey::{Rope, RopeSlice};
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete};
+
+#[derive(Debug, Default, Clone)]
+pub struct Buffer {
+    text: Rope,
+}
+
+impl Buffer {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_reader<R: io::Read>(reader: R) -> io::Result<Self> {
+        Ok ( Self { text: Rope::from_reader(reader)? } )
+    }
+
+    pub fn write_to<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        self.text.write_to(writer)
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.text.len_bytes()
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.text.len_chars()
+    }
+
+    pub fn len_lines(&self) -> usize {
+        self.text.len_lines()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.len_chars() == 0
+    }
+
+    pub fn char(&self, char_idx: usize) -> char {
+        self.text.char(char_idx)
+    }
+
+    pub fn get_char(&self, char_idx: usize) -> Option<char> {
+        self.text.get_char(char_idx)
+    }
+
+    pub fn chunks(&self) -> ropey::iter::Chunks<'_> {
+        self.text.chunks()
+    }
+
+    pub fn slice(&self, chars: Range<usize>) -> RopeSlice<'_> {
+        self.text.slice(chars)
+    }
+
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        self.text.char_to_byte(char_idx)
+    }
+
+    pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+        self.text.byte_to_char(byte_idx)
+    }
+
+    pub fn char_to_line(&self, char_idx: usize) -> usize {
+        self.text.char_to_line(char_idx)
+    }
+
+    pub fn line_to_char(&self, line_idx: usize) -> usize {
+        self.text.line_to_char(line_idx)
+    }
+
+    /// The contents of a line, without its line ending.
+    pub fn line(&self, line_idx: usize) -> Option<RopeSlice<'_>> {
+        let line = self.text.get_line(line_idx)?;
+        Some(line.slice(..line.len_chars() - line_ending_len(&line)))
+    }
+
+    pub fn line_len(&self, line_idx: usize) -> usize {
+        self.line(line_idx).map_or(0, |l| l.len_chars())
+    }
+
+    /// Number of graphemes on a line, not counting the line ending.
+    pub fn line_graphemes(&self, line_idx: usize) -> usize {
+        let start = self.line_to_char(line_idx);
+        let end = start + self.line_len(line_idx);
+        let (mut idx, mut count) = (start, 0);
+        while idx < end {
+            idx = self.next_grapheme_boundary(idx);
+            count += 1;
+        }
+        count
+    }
+
+    /// Char index of the `col`th grapheme on `line_idx`, clamped to the end of the line.
+    pub fn grapheme_to_char(&self, line_idx: usize, col: usize) -> usize {
+        let line_idx = line_idx.min(self.len_lines().saturating_sub(1));
+        let start = self.line_to_char(line_idx);
+        let end = start + self.line_len(line_idx);
+        let mut idx = start;
+        for _ in 0..col {
+            if idx >= end { break; }
+            idx = self.next_grapheme_boundary(idx);
+        }
+        idx.min(end)
+    }
+
+    /// Line and grapheme column of a char index.
+    pub fn char_to_grapheme(&self, char_idx: usize) -> (usize, usize) {
+        let char_idx = char_idx.min(self.len_chars());
+        let line_idx = self.char_to_line(char_idx);
+        let mut idx = self.line_to_char(line_idx);
+        let mut col = 0;
+        while idx < char_idx {
+            idx = self.next_grapheme_boundary(idx);
+            col += 1;
+        }
+        (line_idx, col)
+    }
+
+    pub fn insert(&mut self, char_idx: usize, text: &str) {
+        self.text.insert(char_idx, text)
+    }
+
+    pub fn insert_at_byte(&mut self, byte_idx: usize, text: &str) {
+        let char_idx = self.byte_to_char(byte_idx);
+        self.insert(char_idx, text)
+    }
+
+    pub fn insert_at(&mut self, line_idx: usize, col: usize, text: &str) {
+        let char_idx = self.grapheme_to_char(line_idx, col);
+        self.insert(char_idx, text)
+    }
+
+    pub fn remove(&mut self, chars: Range<usize>) {
+        self.text.remove(chars)
+    }
+
+    pub fn remove_bytes(&mut self, bytes: Range<usize>) {
+        let chars = self.byte_to_char(bytes.start)..self.byte_to_char(bytes.end);
+        self.remove(chars)
+    }
+
+    pub fn remove_graphemes(&mut self, line_idx: usize, cols: Range<usize>) {
+        let start = self.grapheme_to_char(line_idx, cols.start);
+        let end = self.grapheme_to_char(line_idx, cols.end);
+        self.remove(start..end)
+    }
+
+    /// Removes whole lines, including their line endings.
+    pub fn remove_lines(&mut self, lines: Range<usize>) {
+        let end = if lines.end >= self.len_lines() {
+            self.len_chars()
+        } else {
+            self.line_to_char(lines.end)
+        };
+        let start = self.line_to_char(lines.start.min(self.len_lines() - 1));
+        self.remove(start..end)
+    }
+
+    /// Range of the word touching `char_idx`, if there is one.
+    pub fn word_at(&self, char_idx: usize) -> Option<Range<usize>> {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let char_idx = char_idx.min(self.len_chars());
+        let mut start = char_idx;
+        while start > 0 && is_word(self.text.char(start - 1)) {
+            start -= 1;
+        }
+        let mut end = char_idx;
+        while end < self.len_chars() && is_word(self.text.char(end)) {
+            end += 1;
+        }
+        if start == end { None } else { Some(start..end) }
+    }
+
+    /// Char index of the next occurrence of `needle` at or after `from`, wrapping around
+    /// to the start of the buffer.
+    pub fn find(&self, needle: &str, from: usize) -> Option<usize> {
+        let from = self.char_to_byte(from.min(self.len_chars()));
+        self.find_bytes(needle.as_bytes(), from, self.len_bytes())
+            .or_else(|| self.find_bytes(needle.as_bytes(), 0, (from + needle.len()).min(self.len_bytes())))
+            .map(|b| self.byte_to_char(b))
+    }
+
+    /// Byte index of the first occurrence of `needle` within the bytes `from..to`. Reads
+    /// the rope in place, matching across chunk boundaries with Knuth-Morris-Pratt.
+    fn find_bytes(&self, needle: &[u8], from: usize, to: usize) -> Option<usize> {
+        if needle.is_empty() {
+            return Some(from);
+        }
+        let mut fallback = vec![0; needle.len()];
+        let mut k = 0;
+        for i in 1..needle.len() {
+            while k > 0 && needle[i] != needle[k] {
+                k = fallback[k - 1];
+            }
+            if needle[i] == needle[k] {
+                k += 1;
+            }
+            fallback[i] = k;
+        }
+        let mut matched = 0;
+        for (i, byte) in self.text.bytes_at(from).take(to.saturating_sub(from)).enumerate() {
+            while matched > 0 && byte != needle[matched] {
+                matched = fallback[matched - 1];
+            }
+            if byte == needle[matched] {
+                matched += 1;
+            }
+            if matched == needle.len() {
+                return Some(from + i + 1 - needle.len());
+            }
+        }
+        None
+    }
+
+    pub fn next_grapheme_boundary(&self, char_idx: usize) -> usize {
+        if char_idx >= self.len_chars() {
+            return self.len_chars();
+        }
+        let byte_idx = self.char_to_byte(char_idx);
+        let (mut chunk, mut chunk_byte_idx, _, _) = self.text.chunk_at_byte(byte_idx);
+        let mut gc = GraphemeCursor::new(byte_idx, self.len_bytes(), true);
+        loop {
+            match gc.next_boundary(chunk, chunk_byte_idx) {
+                Ok(None) => return self.len_chars(),
+                Ok(Some(n)) => return self.byte_to_char(n),
+                Err(GraphemeIncomplete::NextChunk) => {
+                    chunk_byte_idx += chunk.len();
+                    chunk = self.text.chunk_at_byte(chunk_byte_idx).0;
+                },
+                Err(GraphemeIncomplete::PreContext(n)) => {
+                    let ctx = self.text.chunk_at_byte(n - 1).0;
+                    gc.provide_context(ctx, n - ctx.len());
+                },
+                Err(_) => unreachable!(),
+            }
+        }
+    }
+
+    pub fn prev_grapheme_boundary(&self, char_idx: usize) -> usize {
+        if char_idx == 0 {
+            return 0;
+        }
+        let char_idx = char_idx.min(self.len_chars());
+        let byte_idx = self.char_to_byte(char_idx);
+        let (mut chunk, mut chunk_byte_idx, _, _) = self.text.chunk_at_byte(byte_idx);
+        let mut gc = GraphemeCursor::new(byte_idx, self.len_bytes(), true);
+        loop {
+            match gc.prev_boundary(chunk, chunk_byte_idx) {
+                Ok(None) => return 0,
+                Ok(Some(n)) => return self.byte_to_char(n),
+                Err(GraphemeIncomplete::PrevChunk) => {
+                    let (c, b, _, _) = self.text.chunk_at_byte(chunk_byte_idx - 1);
+                    chunk = c;
+                    chunk_byte_idx = b;
+                },
+                Err(GraphemeIncomplete::PreContext(n)) => {
+                    let ctx = self.text.chunk_at_byte(n - 1).0;
+                    gc.provide_context(ctx, n - ctx.len());
+                },
+                Err(_) => unreachable!(),
+            }
+        }
+    }
+}
+
+impl From<&str> for Buffer {
+    fn from(text: &str) -> Self {
+        Self { text: Rope::from_str(text) }
+    }
+}
+
+impl std::fmt::Display for Buffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for chunk in self.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn line_ending_len(line: &RopeSlice) -> usize {
+    let len = line.len_chars();
+    match (len.checked_sub(2).map(|i| line.char(i)), len.checked_sub(1).map(|i| line.char(i))) {
+        (Some('\r'), Some('\n')) => 2,
+        (_, Some('\n')) | (_, Some('\r')) | (_, Some('\u{000B}')) | (_, Some('\u{000C}'))
+            | (_, Some('\u{0085}')) | (_, Some('\u{2028}')) | (_, Some('\u{2029}')) => 1,
+        _ => 0,
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    backtrace::Backtrace, fs, io::{self, Write}, panic::{self, AssertUnwindSafe, PanicHookInfo},
+    path::{Path, PathBuf}, thread, time::{SystemTime, UNIX_EPOCH},
+};
+use crate::{editor::{Editor, config}, file::OpenFile, term::{TermResult, backend}};
+
+const LOG_FILE: &str = "crash.log";
+const RECOVERY_DIR: &str = "recovery";
+
+/// Where the last panic's message and backtrace are written.
+pub fn log_path() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join(LOG_FILE))
+}
+
+/// Where unsaved changes are written when the editor panics.
+pub fn recovery_dir() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join(RECOVERY_DIR))
+}
+
+/// Restores the terminal before the panic message is printed, so it isn't lost on the
+/// alternate screen, then writes the message and a backtrace to the crash log. Only the
+/// thread that installs the hook owns the terminal: a panic on any other leaves the
+/// editor running, so it just goes to the crash log.
+pub fn install_hook() {
+    let default = panic::take_hook();
+    let editor = thread::current().id();
+    panic::set_hook(Box::new(move |info| {
+        if thread::current().id() != editor {
+            if let Some(path) = log_path() {
+                let _ = write_log(&path, info);
+            }
+            return;
+        }
+        backend::restore();
+        default(info);
+        if let Some(path) = log_path() {
+            match write_log(&path, info) {
+                Ok(()) => eprintln!("Crash log written to {}", path.display()),
+                Err(e) => eprintln!("Could not write crash log to {}: {}", path.display(), e),
+            }
+        }
+    }));
+}
+
+fn write_log(path: &Path, info: &PanicHookInfo) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut log = fs::File::create(path)?;
+    writeln!(log, "ded {} panicked at {} (unix time)", env!("CARGO_PKG_VERSION"), now())?;
+    writeln!(log, "{}", info)?;
+    writeln!(log, "\n{}", Backtrace::force_capture())
+}
+
+/// Runs `editor` until it stops. Unless the user quit, whether it stopped on an error, a
+/// panic or the end of input, unsaved changes are recovered before it returns or the
+/// panic carries on.
+pub fn run(editor: &mut Editor) -> TermResult<()> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| editor.run()));
+    if !editor.has_quit() || !matches!(result, Ok(Ok(()))) {
+        recover(editor);
+    }
+    match result {
+        Ok(result) => result,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// Writes every file with unsaved changes to the recovery directory, telling the user
+/// where each went. The terminal is restored first so that can be read.
+pub fn recover(editor: &Editor) {
+    if editor.dirty_files().next().is_none() {
+        return;
+    }
+    backend::restore();
+    let dir = match recovery_dir() {
+        Some(dir) => dir,
+        None => return eprintln!("No directory to recover unsaved changes to"),
+    };
+    let stamp = now();
+    for file in editor.dirty_files() {
+        let name = file.path().file_name().map_or("untitled".into(), |n| n.to_string_lossy());
+        let to = dir.join(format!("{}-{}", stamp, name));
+        match write_recovery(file, &to) {
+            Ok(()) => eprintln!("Unsaved changes to {} written to {}", display(file), to.display()),
+            Err(e) => eprintln!("Could not recover {}: {}", display(file), e),
+        }
+    }
+}
+
+fn write_recovery(file: &OpenFile, to: &Path) -> io::Result<()> {
+    if let Some(dir) = to.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    file.buffer().write_to(fs::File::create(to)?)
+}
+
+fn display(file: &OpenFile) -> String {
+    match file.path().as_os_str().is_empty() {
+        true => "[untitled]".into(),
+        false => file.path().display().to_string(),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
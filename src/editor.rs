@@ -2,110 +2,954 @@ pub mod action;
 pub mod row;
 pub mod theme;
 pub mod config;
+pub mod cursor;
+pub mod selection;
+pub mod textobj;
+pub mod motion;
+pub mod register;
+pub mod clipboard;
+pub mod status;
+pub mod message;
+pub mod command;
+pub mod keymap;
+pub mod mode;
+pub mod macros;
 
-use std::{thread, sync, io::{self, Read, Write}};
+use std::io;
 use crate::{
-    term::{Term, TermOp},
+    term::{CursorShape, Term, TermOp, TermResult, frame::{Frame, Style}},
     file::OpenFile,
     editor::row::Row,
-    editor::action::Action,
+    editor::action::{Action, Direction, RelativeLocation, Target},
+    editor::config::Config,
+    editor::cursor::Cursor,
+    editor::selection::{Selection, Selections},
+    editor::register::{Registers, Yank},
+    editor::clipboard::Clipboard,
+    editor::message::{Level, Messages},
+    editor::command::Registry,
+    editor::keymap::{KeyChord, Keymap, Lookup},
+    editor::macros::Macros,
+    editor::mode::{Mode, Operator, Visual},
+    event::{
+        key, mouse::{Clicks, Unit}, channel::{Events, Message, Timer}, process::{self, Output},
+        watch::Watcher,
+    },
+    prompt::{self, Prompt, PromptEvent, PromptHistory, PromptKind},
+    buffer::Buffer,
 };
+use std::collections::HashMap;
 use std::env;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
 use crossterm::{
-    execute, write_ansi_code, cursor, cursor::*,
-    event::{KeyCode, KeyEvent, KeyModifiers, read, poll},
-    style::Color, Result as TermResult,
+    event::{Event, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    style::{Attribute, Color},
 };
 
+/// Lines the mouse wheel scrolls by.
+const SCROLL_LINES: isize = 3;
+
+/// What to do with the input of a submitted prompt.
+type OnSubmit = fn(&mut Editor, String) -> TermResult<()>;
+
 #[derive(Debug)]
 pub struct Editor{
     quit: bool,
-    insert: bool,
+    mode: Mode,
+    /// The count typed for the next action, and the one typed before a pending operator.
+    count: Option<usize>,
+    op_count: usize,
+    /// Where a visual block was started and the corner opposite.
+    block: (Cursor, Cursor),
+    cursor_shape: Option<CursorShape>,
     term: Term,
-    cursor: Coords,
+    events: Events,
+    watcher: Watcher,
+    /// Running child processes, by id, with the commands they were started with.
+    processes: HashMap<usize, String>,
+    next_process: usize,
+    config: Config,
+    selections: Selections,
+    registers: Registers,
+    clipboard: Clipboard,
+    messages: Messages,
+    prompt: Option<(Prompt, OnSubmit)>,
+    prompt_history: PromptHistory,
+    commands: Registry,
+    keymap: Keymap,
+    macros: Macros,
+    /// Keys of a sequence typed so far, and when the last one was.
+    pending: Vec<KeyChord>,
+    pending_since: Instant,
+    clicks: Clicks,
+    /// What a mouse drag selects by, and what it started on.
+    drag: Option<(Unit, Range<usize>)>,
+    /// The char ranges the last paste inserted, one per selection, and whether it was
+    /// linewise.
+    last_paste: Option<(Vec<Range<usize>>, bool)>,
     offset: Coords,
-    action: Action,
     files: Vec<OpenFile>,
     file_idx: usize,
 }
 
 impl Editor {
 
+    /// Opens `files` on `term`, or an empty buffer if there are none, with the user's
+    /// options and keymap from the config directory. Errors in those are posted as messages.
+    pub fn with_user_config(term: Term, files: Vec<OpenFile>) -> TermResult<Self> {
+        let mut errors = Vec::new();
+        let mut config = Config::new();
+        if let Some(path) = Config::options_path() {
+            errors.extend(config.load_file(&path));
+        }
+        let mut keymap = Keymap::new();
+        if let Some(path) = Keymap::config_path() {
+            errors.extend(keymap.load_file(&path));
+        }
+        let mut editor = Self::new(term, files, config, keymap)?;
+        for error in errors {
+            editor.messages.error(error);
+        }
+        Ok(editor)
+    }
+
+    /// Opens `files` on `term` with `config` and `keymap`, or an empty buffer if there are
+    /// none. Nothing is read from the config directory.
+    pub fn new(mut term: Term, mut files: Vec<OpenFile>, config: Config, keymap: Keymap) -> TermResult<Self> {
+        if files.is_empty() {
+            files.push(OpenFile::default());
+        }
+        for file in files.iter_mut() {
+            file.set_undo_file(config.undo_file);
+        }
+        let clipboard = Clipboard::new(config.clipboard.clone());
+        term.init()?;
+        let events = Events::new();
+        let watcher = Watcher::new(events.sender());
+        for file in files.iter().filter(|f| !f.is_scratch() && !f.path().as_os_str().is_empty()) {
+            watcher.watch(file.path());
+        }
+        Ok ( Self {
+            quit: false,
+            mode: Mode::Normal,
+            count: None,
+            op_count: 1,
+            block: (Cursor::default(), Cursor::default()),
+            cursor_shape: None,
+            events,
+            watcher,
+            processes: HashMap::new(),
+            next_process: 0,
+            config,
+            selections: Selections::default(),
+            registers: Registers::new(),
+            clipboard,
+            messages: Messages::new(),
+            prompt: None,
+            prompt_history: PromptHistory::new(),
+            commands: Registry::new(),
+            keymap,
+            macros: Macros::new(),
+            pending: Vec::new(),
+            pending_since: Instant::now(),
+            clicks: Clicks::new(),
+            drag: None,
+            last_paste: None,
+            offset: Coords::default(),
+            file_idx: 0,
+            files, term,
+        })
+    }
+
+    /// Runs until the editor quits or the terminal runs out of input.
     pub fn run(&mut self) -> TermResult<()> {
-        while self.quit != false {
+        self.term.listen(self.events.sender());
+        while !self.quit {
+            self.scroll();
             self.refresh()?;
-            self.process_key()?;
+            if !self.process_event()? {
+                break;
+            }
         }
         Ok(())
     }
 
-    fn refresh(&self) -> TermResult<()> {
-        Term::ex(TermOp::CursorEnabled(false))?;
-        Term::ex(TermOp::SetCursor(Coords::default()))?;
+    fn refresh(&mut self) -> TermResult<()> {
         if self.quit {
-            Term::ex(TermOp::ClearLn)?;
+            return Ok(());
+        }
+        let mut frame = Frame::new(self.term.dims.clone());
+        self.draw_rows(&mut frame);
+        self.draw_status(&mut frame);
+        frame.cursor = Some(Coords {
+            x: self.cursor_x().saturating_sub(self.offset.x) + self.gutter_width(),
+            y: self.cursor().line.saturating_sub(self.offset.y),
+        });
+        self.draw_msg(&mut frame);
+        let shape = if self.prompt.is_some() { CursorShape::Bar } else { self.mode().cursor_shape() };
+        if self.config.modal && self.cursor_shape != Some(shape) {
+            self.term.ex(TermOp::CursorShape(shape))?;
+            self.cursor_shape = Some(shape);
+        }
+        self.term.draw(&frame)
+    }
+
+    fn draw_row(&self, frame: &mut Frame, y: usize, idx: usize, row: &Row) {
+        let tab_width = self.config.tab_width;
+        let (mut x, end) = (self.offset.x, self.offset.x + self.text_width());
+        let mut col = self.gutter_width();
+        for (a, b) in self.selection_spans(idx, row) {
+            let (a, b) = (a.clamp(x, end), b.clamp(x, end));
+            col = frame.print(col, y, &row.render(x, a - x, tab_width), Style::default());
+            let selected = Style::default().attr(Attribute::Reverse);
+            let start = col;
+            col = frame.print(col, y, &row.render(a, b - a, tab_width), selected);
+            let pad = (b - a).saturating_sub(col - start);
+            col = frame.print(col, y, &" ".repeat(pad), selected);
+            x = b;
+        }
+        frame.print(col, y, &row.render(x, end - x, tab_width), Style::default());
+    }
+
+    /// Display columns of `row` covered by a selection, or by a cursor other than the
+    /// primary one, which the terminal cursor already shows.
+    fn selection_spans(&self, idx: usize, row: &Row) -> Vec<(usize, usize)> {
+        let file = self.curr_file();
+        let tab_width = self.config.tab_width;
+        let primary = self.selections.primary();
+        let mut spans = Vec::new();
+        for sel in self.selections.iter() {
+            let range = self.selection_range(sel);
+            let (start, end) = (file.coords(range.start), file.coords(range.end));
+            if idx < start.y || idx > end.y || (sel.is_empty() && sel == primary) {
+                continue;
+            }
+            let from = if idx == start.y { row.width_to(start.x, tab_width) } else { 0 };
+            let to = if sel.is_empty() {
+                from + 1
+            } else if idx == end.y {
+                row.width_to(end.x, tab_width)
+            } else {
+                row.width_to(row.len(), tab_width) + 1
+            };
+            spans.push((from, to));
+        }
+        spans
+    }
+
+    fn cursor(&self) -> &Cursor {
+        &self.selections.primary().head
+    }
+
+    fn cursor_x(&self) -> usize {
+        self.cursor().display_col(self.curr_file(), self.config.tab_width)
+    }
+
+    fn text_height(&self) -> usize {
+        self.term.dims.y.saturating_sub(2)
+    }
+
+    fn text_width(&self) -> usize {
+        self.term.dims.x.saturating_sub(self.gutter_width())
+    }
+
+    /// Columns taken by line numbers, with a space after them.
+    fn gutter_width(&self) -> usize {
+        if self.config.line_numbers {
+            self.curr_file().len().to_string().len().max(3) + 1
         } else {
-            self.draw_rows()?;
-            self.draw_status()?;
-            self.draw_msg()?;
-            Term::ex(TermOp::SetCursor(Coords {
-                x: self.cursor.x.saturating_sub(self.offset.x),
-                y: self.cursor.y.saturating_sub(self.offset.y),
-            }))?;
+            0
         }
-        Term::ex(TermOp::CursorEnabled(true))?;
-        Term::ex(TermOp::Flush)
     }
 
-    fn draw_row(&self, row: &Row) {
-        let (start, end) =
-            (self.offset.x,
-             self.offset.x + self.term.dims.x);
-        let row = row.render(start, end);
-        println!("{}\r", row)
+    pub fn is_insert(&self) -> bool {
+        self.mode() == Mode::Insert
+    }
+
+    /// The current mode. Without modal editing, that's always insert mode.
+    pub fn mode(&self) -> Mode {
+        if self.config.modal { self.mode } else { Mode::Insert }
+    }
+
+    /// The keymap layer keys are looked up in.
+    fn keymap_mode(&self) -> &'static str {
+        if self.prompt.is_some() { "prompt" } else { self.mode().name() }
+    }
+
+    pub fn count(&self) -> Option<usize> {
+        self.count
     }
 
     fn curr_file(&self) -> &OpenFile {
-        self.files.get(self.file_idx as usize).unwrap()
+        self.files.get(self.file_idx).unwrap()
     }
 
-    fn draw_rows(&self) -> TermResult<()> {
-        let t_height = self.term.dims.y;
+    fn curr_file_mut(&mut self) -> &mut OpenFile {
+        self.files.get_mut(self.file_idx).unwrap()
+    }
+
+    fn draw_rows(&self, frame: &mut Frame) {
+        let t_height = self.text_height();
         for row_idx in 0..t_height {
-            Term::ex(TermOp::ClearLn)?;
             let curr = self.curr_file();
             if let Some(row) = curr.get(row_idx + self.offset.y) {
-                self.draw_row(&row);
+                self.draw_gutter(frame, row_idx, row_idx + self.offset.y);
+                self.draw_row(frame, row_idx, row_idx + self.offset.y, &row);
             } else if self.curr_file().is_empty() && row_idx == t_height / 3 {
-                self.welcome();
+                self.welcome(frame, row_idx);
             } else {
-                println!("~\r");
+                frame.print(0, row_idx, "~", Style::default());
+            }
+        }
+    }
+
+    fn draw_gutter(&self, frame: &mut Frame, y: usize, idx: usize) {
+        let width = self.gutter_width();
+        if width > 0 {
+            let number = format!("{:>1$} ", idx + 1, width - 1);
+            frame.print(0, y, &number, Style::default().fg(Color::DarkGrey));
+        }
+    }
+
+    fn draw_status(&self, frame: &mut Frame) {
+        let style = Style::default().bg(Color::Cyan).fg(Color::Black);
+        let line = status::render(self, &self.config.status, self.term.dims.x);
+        let x = frame.print(0, self.text_height(), &line, style);
+        frame.fill(x, self.text_height(), style);
+    }
+
+    /// Handles the next message on the event channel, returning false once there is no
+    /// more input. Timers wake it when the shown message or a pending key sequence times out.
+    pub fn process_event(&mut self) -> TermResult<bool> {
+        let now = Instant::now();
+        match self.pending_left() {
+            Some(left) => self.events.set_timer(Timer::Keys, now + left),
+            None => self.events.cancel_timer(Timer::Keys),
+        }
+        match self.messages.time_left(self.config.message_timeout) {
+            Some(left) => self.events.set_timer(Timer::Message, now + left),
+            None => self.events.cancel_timer(Timer::Message),
+        }
+        let message = if self.term.is_listening() {
+            self.events.wait()
+        } else {
+            match self.events.try_next() {
+                Some(message) => message,
+                None => match self.next_input()? {
+                    Some(message) => message,
+                    None => return Ok(false),
+                },
             }
+        };
+        match message {
+            Message::Input(event) => self.input(event)?,
+            Message::Closed => return Ok(false),
+            Message::Timer(Timer::Keys) => if self.pending_left() == Some(Duration::from_millis(0)) {
+                self.flush_pending()?;
+            },
+            // Redrawing takes the message away.
+            Message::Timer(Timer::Message) => {},
+            Message::FileChanged(path) => self.file_changed(path),
+            Message::Process(id, output) => self.process_output(id, output),
+            Message::Lsp(server, message) => self.lsp_message(&server, &message),
+        }
+        Ok(true)
+    }
+
+    /// Reads the terminal directly when it doesn't send input down the channel, waking
+    /// for the earliest timer.
+    fn next_input(&mut self) -> TermResult<Option<Message>> {
+        let timeout = [self.messages.time_left(self.config.message_timeout), self.pending_left()]
+            .iter()
+            .flatten()
+            .min()
+            .copied();
+        if let Some(left) = timeout {
+            if !self.term.poll_event(left)? {
+                return Ok(self.events.try_next().or(Some(Message::Timer(Timer::Keys))));
+            }
+        }
+        Ok(self.term.read_event()?.map(Message::Input))
+    }
+
+    fn input(&mut self, event: Event) -> TermResult<()> {
+        match event {
+            Event::Key(key) if key.kind != KeyEventKind::Release => self.press(key.into()),
+            Event::Paste(text) => self.paste_input(&text),
+            Event::Resize(width, height) => {
+                self.resize((width, height).into());
+                Ok(())
+            },
+            Event::Mouse(event) => self.mouse(event),
+            _ => Ok(()),
+        }
+    }
+
+    /// A sender for background work, such as a language server client, to report to the
+    /// editor with.
+    pub fn event_sender(&self) -> Sender<Message> {
+        self.events.sender()
+    }
+
+    fn file_changed(&mut self, path: PathBuf) {
+        if self.files.iter().any(|f| f.path() == path && !f.is_scratch() && f.changed_on_disk()) {
+            self.messages.warn(format!("{} changed on disk", path.display()));
         }
+    }
+
+    /// Runs `command` through the shell in the background, its output going to the
+    /// `[shell]` buffer as it comes.
+    pub fn run_shell(&mut self, command: &str) -> Result<(), String> {
+        let id = self.next_process;
+        process::spawn(id, command, self.events.sender()).map_err(|e| format!("{}: {}", command, e))?;
+        self.next_process += 1;
+        self.processes.insert(id, command.to_string());
+        self.show_scratch("[shell]", &format!("$ {}\n", command));
         Ok(())
     }
 
-    fn draw_status(&self) -> TermResult<()> {
-        Term::ex(TermOp::SetBg(Color::Cyan))?;
-        println!("{}\r", " ".repeat(self.term.dims.x as usize));
-        Term::ex(TermOp::SetBg(Color::Reset))?;
+    fn process_output(&mut self, id: usize, output: Output) {
+        let line = match output {
+            Output::Stdout(line) | Output::Stderr(line) => line,
+            Output::Exit(code) => {
+                let command = self.processes.remove(&id).unwrap_or_default();
+                match code {
+                    Some(0) => self.messages.info(format!("{} finished", command)),
+                    Some(code) => self.messages.warn(format!("{} exited with {}", command, code)),
+                    None => self.messages.warn(format!("{} was killed", command)),
+                }
+                return;
+            },
+        };
+        // Output of an older run, or of a run whose buffer was closed, is dropped.
+        if id + 1 != self.next_process {
+            return;
+        }
+        if let Some(file) = self.files.iter_mut().find(|f| f.is_scratch() && f.path().as_os_str() == "[shell]") {
+            let end = file.buffer().len_chars();
+            file.insert(end, &format!("{}\n", line));
+        }
+    }
+
+    /// Shows what a language server asks to show the user. Other notifications are left
+    /// to whatever talks to the server.
+    fn lsp_message(&mut self, server: &str, message: &serde_json::Value) {
+        if message["method"] != "window/showMessage" {
+            return;
+        }
+        let params = &message["params"];
+        let text = format!("{}: {}", server, params["message"].as_str().unwrap_or_default());
+        match params["type"].as_u64() {
+            Some(1) => self.messages.error(text),
+            Some(2) => self.messages.warn(text),
+            _ => self.messages.info(text),
+        }
+    }
+
+    /// Adds `chord` to the pending keys and runs what they're bound to once they make up
+    /// a whole binding.
+    fn press(&mut self, chord: KeyChord) -> TermResult<()> {
+        self.pending.push(chord);
+        match self.keymap.lookup(&self.pending, self.keymap_mode(), self.curr_file().filetype()) {
+            Lookup::Found(actions) => {
+                self.pending.clear();
+                self.run_actions(actions)
+            },
+            // A recording stops on the key that starts one, without waiting for a register.
+            Lookup::Prefix(Some(actions)) if self.macros.recording().is_some()
+                && actions.actions().iter().all(|a| matches!(a, Action::Record(_))) => {
+                self.pending.clear();
+                self.run_actions(actions)
+            },
+            Lookup::Prefix(_) => {
+                self.pending_since = Instant::now();
+                Ok(())
+            },
+            Lookup::Unbound => {
+                let keys = std::mem::take(&mut self.pending);
+                self.replay(keys)
+            },
+        }
+    }
+
+    /// Runs keys that don't make up a binding: the longest run of them from the start
+    /// that is bound runs its actions, or else the first key does what it does unbound,
+    /// and the keys after it are pressed again.
+    fn replay(&mut self, keys: Vec<KeyChord>) -> TermResult<()> {
+        let (mode, filetype) = (self.keymap_mode(), self.curr_file().filetype());
+        let bound = (1..=keys.len()).rev().find_map(|n| match self.keymap.lookup(&keys[..n], mode, filetype) {
+            Lookup::Found(actions) | Lookup::Prefix(Some(actions)) => Some((n, actions)),
+            _ => None,
+        });
+        let n = match bound {
+            Some((n, actions)) => {
+                self.run_actions(actions)?;
+                n
+            },
+            None => {
+                self.run_actions(Keymap::fallback(keys[0], mode).into())?;
+                1
+            },
+        };
+        for chord in &keys[n..] {
+            self.press(*chord)?;
+        }
+        Ok(())
+    }
+
+    /// Lays the editor out for a new terminal size. The view is pulled back so it doesn't
+    /// run past the end of the file, then follows the cursor.
+    fn resize(&mut self, dims: Coords) {
+        self.term.resize(dims);
+        let (width, height) = (self.text_width(), self.text_height());
+        self.offset.y = self.offset.y.min(self.curr_file().len().saturating_sub(height));
+        self.offset.x = self.offset.x.min(self.cursor_x().saturating_sub(width.saturating_sub(1)));
+        self.scroll();
+    }
+
+    /// Inserts text pasted into the terminal at every selection, as one undo step. It
+    /// doesn't go through the keymap, so pasted text never runs bindings.
+    fn paste_input(&mut self, text: &str) -> TermResult<()> {
+        self.pending.clear();
+        // Line breaks would submit a prompt, so it gets the first line only.
+        if self.prompt.is_some() {
+            let line = text.lines().next().unwrap_or_default();
+            return self.prompt_action(Action::Input(line.to_string()));
+        }
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let ending = self.curr_file().line_ending();
+        let text = if ending == "\n" { text } else { text.replace('\n', ending) };
+        self.macros.record(&Action::Input(text.clone()));
+        self.curr_file_mut().seal_history();
+        self.edit_each(|file, range| {
+            let at = file.remove(range);
+            file.insert(at, &text)
+        });
+        self.curr_file_mut().seal_history();
         Ok(())
     }
 
-    pub fn process_key(&mut self) -> TermResult<()> {
-        Self::execute(Term::read_key()?)?;
+    /// Clicks place the cursor, or select a word or line when doubled or tripled, and
+    /// dragging extends the selection. The wheel scrolls. Clicks on the gutter select
+    /// lines, and those on the status line go to the segment clicked.
+    fn mouse(&mut self, event: MouseEvent) -> TermResult<()> {
+        let height = self.text_height();
+        let (x, y) = (event.column as usize, event.row as usize);
+        match event.kind {
+            MouseEventKind::ScrollDown => self.scroll_view(SCROLL_LINES),
+            MouseEventKind::ScrollUp => self.scroll_view(-SCROLL_LINES),
+            MouseEventKind::Down(_) | MouseEventKind::Drag(_) if self.prompt.is_some() => {},
+            MouseEventKind::Down(MouseButton::Left) if y == height => {
+                let segment = status::segment_at(self, &self.config.status, self.term.dims.x, x);
+                if let Some(action) = segment.and_then(|s| s.on_click()) {
+                    self.execute(action)?;
+                }
+            },
+            MouseEventKind::Down(MouseButton::Left) if y > height => self.show_messages(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.pending.clear();
+                self.count = None;
+                let at = self.char_at(x, y);
+                let anchor = self.curr_file().char_idx(&self.selections.primary().anchor.coords());
+                let extend = event.modifiers.contains(KeyModifiers::SHIFT);
+                let (unit, from) = if x < self.gutter_width() {
+                    (Unit::Line, self.unit_at(Unit::Line, at))
+                } else if extend {
+                    (Unit::Char, anchor..anchor)
+                } else {
+                    let unit = self.clicks.press((event.column, event.row));
+                    (unit, self.unit_at(unit, at))
+                };
+                if unit == Unit::Char && !extend && matches!(self.mode(), Mode::Visual(_) | Mode::Pending(_)) {
+                    self.set_mode(Mode::Normal);
+                }
+                self.drag_to(unit, from.clone(), at);
+                self.drag = Some((unit, from));
+            },
+            MouseEventKind::Drag(MouseButton::Left) => if let Some((unit, from)) = self.drag.clone() {
+                let at = self.char_at(x, y);
+                self.drag_to(unit, from, at);
+            },
+            MouseEventKind::Up(MouseButton::Left) => self.drag = None,
+            _ => {},
+        }
         Ok(())
     }
 
-    fn draw_msg(&self) -> TermResult<()> {
-        Term::ex(TermOp::ClearLn)?;
+    /// The char under screen cell `x`, `y`, or the nearest one in the text.
+    fn char_at(&self, x: usize, y: usize) -> usize {
+        let file = self.curr_file();
+        let line = (self.offset.y + y).min(file.len().saturating_sub(1));
+        let x = self.offset.x + x.saturating_sub(self.gutter_width());
+        let col = file.get(line).map_or(0, |row| row.col_at(x, self.config.tab_width));
+        file.char_idx(&Coords { x: col, y: line })
+    }
+
+    /// The chars of the `unit` at `at`.
+    fn unit_at(&self, unit: Unit, at: usize) -> Range<usize> {
+        let buffer = self.curr_file().buffer();
+        match unit {
+            Unit::Char => at..at,
+            Unit::Word => buffer.word_at(at).unwrap_or(at..(at + 1).min(buffer.len_chars())),
+            Unit::Line => {
+                let line = buffer.char_to_line(at);
+                let end = if line + 1 < buffer.len_lines() { buffer.line_to_char(line + 1) } else { buffer.len_chars() };
+                buffer.line_to_char(line)..end
+            },
+        }
+    }
+
+    /// Selects from the unit a drag started on, `from`, to the one at `at`. In modal
+    /// editing that goes into visual mode, unless the drag hasn't left the click.
+    fn drag_to(&mut self, unit: Unit, from: Range<usize>, at: usize) {
+        let to = self.unit_at(unit, at);
+        if self.config.modal && self.mode != Mode::Insert && (unit != Unit::Char || to != from) {
+            let visual = if unit == Unit::Line { Visual::Line } else { Visual::Char };
+            if self.mode != Mode::Visual(visual) {
+                self.set_mode(Mode::Visual(visual));
+            }
+        }
+        let (mut anchor, mut head) = if to.start < from.start { (from.end, to.start) } else { (from.start, to.end) };
+        // Visual mode selections take in the char under the cursor.
+        if matches!(self.mode(), Mode::Visual(_)) && unit != Unit::Char {
+            if head > anchor {
+                head -= 1;
+            } else if anchor > head {
+                anchor -= 1;
+            }
+        }
+        let file = &self.files[self.file_idx];
+        let mut sel = Selection::default();
+        sel.anchor.set(file.coords(anchor));
+        sel.head.set(file.coords(head));
+        self.selections.set(sel);
+    }
+
+    /// Scrolls the view by `by` lines, moving the cursor along when it would go off screen.
+    fn scroll_view(&mut self, by: isize) {
+        let last = self.curr_file().len().saturating_sub(1);
+        self.offset.y = (self.offset.y as isize + by).clamp(0, last as isize) as usize;
+        let (line, top) = (self.cursor().line, self.offset.y);
+        let bottom = top + self.text_height().saturating_sub(1);
+        let dir = if line < top {
+            Direction::Down((top - line) as u16)
+        } else if line > bottom {
+            Direction::Up((line - bottom) as u16)
+        } else {
+            return;
+        };
+        match self.mode() {
+            Mode::Visual(Visual::Block) => self.block_motion(&dir),
+            Mode::Visual(_) => self.extend_motion(&dir),
+            _ => self.motion(&dir, false),
+        }
+    }
+
+    /// How long until the pending keys time out, if there are any.
+    fn pending_left(&self) -> Option<Duration> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some((self.pending_since + self.config.key_timeout).saturating_duration_since(Instant::now()))
+    }
+
+    /// Gives up waiting for more keys, running the pending ones as they are.
+    fn flush_pending(&mut self) -> TermResult<()> {
+        let keys = std::mem::take(&mut self.pending);
+        self.replay(keys)
+    }
+
+    pub fn pending_keys(&self) -> &[KeyChord] {
+        &self.pending
+    }
+
+    /// Runs a binding's actions in order. In normal and visual mode a count repeats them,
+    /// unless they use the count themselves.
+    pub fn run_actions(&mut self, actions: key::Action) -> TermResult<()> {
+        let repeats = self.prompt.is_none()
+            && matches!(self.mode(), Mode::Normal | Mode::Visual(_))
+            && !actions.actions().iter()
+                .any(|a| matches!(a, Action::Count(_) | Action::Operator(_) | Action::SetMode(_) | Action::Append));
+        let times = if repeats { self.count.take().unwrap_or(1) } else { 1 };
+        for action in actions.actions() {
+            self.macros.record(action);
+        }
+        for _ in 0..times {
+            for action in actions.clone() {
+                self.execute(action)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn recording(&self) -> Option<char> {
+        self.macros.recording()
+    }
+
+    /// Starts recording into `register`, or stops and keeps what was recorded as text.
+    fn record(&mut self, register: Option<char>) {
+        if let Some((name, actions)) = self.macros.stop() {
+            self.registers.set(name, Yank::new(vec![macros::to_text(&actions)], true));
+            return;
+        }
+        match register.unwrap_or_else(|| self.macros.last()) {
+            name if name.is_ascii_alphanumeric() => self.macros.start(name),
+            name => self.messages.warn(format!("Can't record into register {}", name)),
+        }
+    }
+
+    /// The actions of the macro in `register`, posting an error if there are none.
+    pub fn macro_actions(&mut self, register: char) -> Option<Vec<Action>> {
+        let text = match self.registers.get(register) {
+            Some(yank) => yank.joined(),
+            None => {
+                self.messages.warn(format!("Register {} is empty", register));
+                return None;
+            },
+        };
+        match macros::parse(&text) {
+            Ok(actions) => Some(actions),
+            Err(e) => {
+                self.messages.error(format!("Macro @{}: {}", register, e));
+                None
+            },
+        }
+    }
+
+    /// Replays the macro in `register`, once for every selected line in visual mode or
+    /// when a selection spans lines.
+    fn replay_macro(&mut self, register: Option<char>) -> TermResult<()> {
+        let register = register.unwrap_or_else(|| self.macros.last());
+        let actions = match self.macro_actions(register) {
+            Some(actions) => actions,
+            None => return Ok(()),
+        };
+        self.macros.set_last(register);
+        match self.selected_lines() {
+            Some(lines) => self.replay_lines(&actions, lines),
+            None => self.run_macro(&actions),
+        }
+    }
+
+    pub fn run_macro(&mut self, actions: &[Action]) -> TermResult<()> {
+        if let Err(e) = self.macros.enter() {
+            self.messages.error(e);
+            return Ok(());
+        }
+        let result = actions.iter().try_for_each(|action| self.run_actions(action.clone().into()));
+        self.macros.leave();
+        result
+    }
+
+    /// Runs `actions` from the start of each of `lines` in turn, following the lines as
+    /// the macro adds or removes some.
+    pub fn replay_lines(&mut self, actions: &[Action], lines: Range<usize>) -> TermResult<()> {
+        let (mut line, mut end) = (lines.start as isize, lines.end as isize);
+        if self.config.modal {
+            self.set_mode(Mode::Normal);
+        }
+        while line < end && (line as usize) < self.curr_file().len() {
+            let before = self.curr_file().len() as isize;
+            self.selections.set(Selection::point(Cursor::new(line as usize, 0)));
+            self.run_macro(actions)?;
+            if self.config.modal && self.prompt.is_none() {
+                self.set_mode(Mode::Normal);
+            }
+            let delta = self.curr_file().len() as isize - before;
+            end += delta;
+            line += 1 + delta;
+        }
+        Ok(())
+    }
+
+    /// The lines a macro replays over: those of the selections in visual mode, or when
+    /// the selections span more than a line.
+    fn selected_lines(&self) -> Option<Range<usize>> {
+        let file = self.curr_file();
+        let visual = matches!(self.mode(), Mode::Visual(_)) && self.config.modal;
+        let (first, last) = self.selections.iter()
+            .map(|sel| {
+                let range = sel.range(file);
+                let (first, mut last) = (file.coords(range.start).y, file.coords(range.end).y);
+                // Without visual mode a selection ending at the start of a line stops
+                // before it.
+                if !visual && last > first && file.coords(range.end).x == 0 {
+                    last -= 1;
+                }
+                (first, last)
+            })
+            .fold((usize::MAX, 0), |(a, b), (first, last)| (a.min(first), b.max(last)));
+        if visual || last > first {
+            Some(first..last + 1)
+        } else {
+            None
+        }
+    }
+
+    fn draw_msg(&self, frame: &mut Frame) {
+        let y = self.text_height() + 1;
+        let mut x = 0;
+        if let Some((prompt, _)) = &self.prompt {
+            let (line, cursor) = prompt.render(self.term.dims.x);
+            x = frame.print(0, y, &line, Style::default());
+            frame.cursor = Some(Coords { x: cursor, y });
+        } else if let Some(msg) = self.messages.current(self.config.message_timeout) {
+            let style = match msg.level {
+                Level::Info => Style::default(),
+                Level::Warning => Style::default().fg(Color::Yellow),
+                Level::Error => Style::default().fg(Color::Red),
+            };
+            x = frame.print(0, y, msg.text.lines().next().unwrap_or_default(), style);
+        }
+        frame.fill(x, y, Style::default());
+    }
+
+    /// Lets any part of the editor tell the user something.
+    pub fn messages_mut(&mut self) -> &mut Messages {
+        &mut self.messages
+    }
+
+    /// Opens `prompt` in the message line. Actions go to it until it is submitted, when
+    /// `on_submit` gets its input, or cancelled.
+    pub fn open_prompt(&mut self, prompt: Prompt, on_submit: OnSubmit) {
+        self.prompt = Some((prompt, on_submit));
+    }
+
+    fn prompt_action(&mut self, action: Action) -> TermResult<()> {
+        let (prompt, on_submit) = match self.prompt.as_mut() {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let kind = prompt.kind();
+        match prompt.handle(&action, self.prompt_history.get(kind)) {
+            PromptEvent::Pending => Ok(()),
+            PromptEvent::Cancel => {
+                self.prompt = None;
+                Ok(())
+            },
+            PromptEvent::Submit(input) => {
+                let on_submit = *on_submit;
+                self.prompt = None;
+                self.prompt_history.push(kind, &input);
+                on_submit(self, input)
+            },
+        }
+    }
+
+    fn open_kind(&mut self, kind: PromptKind) {
+        match kind {
+            PromptKind::Command => {
+                let names = self.commands.names();
+                let prompt = Prompt::new(kind).with_completer(move |input| {
+                    names.iter().filter(|n| n.starts_with(input)).map(|n| n.to_string()).collect()
+                });
+                self.open_prompt(prompt, Self::run_command)
+            },
+            PromptKind::Path => {
+                let prompt = Prompt::new(kind).with_completer(prompt::complete_path);
+                self.open_prompt(prompt, |editor, path| {
+                    editor.open_path(&path);
+                    Ok(())
+                })
+            },
+            PromptKind::Search => self.open_prompt(Prompt::new(kind), |editor, needle| {
+                editor.search(&needle);
+                Ok(())
+            }),
+            PromptKind::Confirm => {},
+        }
+    }
+
+    /// Runs a command line, as typed at the command prompt or bound to a key.
+    fn run_command(&mut self, line: String) -> TermResult<()> {
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        let current = self.cursor().line;
+        let last = self.curr_file().len().saturating_sub(1);
+        let result = self.commands.parse(&line, current, last)
+            .and_then(|(command, invocation)| (command.run)(self, &invocation));
+        if let Err(e) = result {
+            self.messages.error(e);
+        }
         Ok(())
     }
 
+    /// Switches to the next or previous open file, wrapping around.
+    fn switch_file(&mut self, to: RelativeLocation) {
+        let len = self.files.len() as isize;
+        let by = match to {
+            RelativeLocation::Next => 1,
+            RelativeLocation::Previous => -1,
+            RelativeLocation::DiffIndex(n) => n as isize,
+        };
+        self.set_file_idx((self.file_idx as isize + by).rem_euclid(len) as usize);
+    }
+
+    /// Switches to the file at `path`, opening it first if it isn't open yet.
+    fn open_path(&mut self, path: &str) {
+        if path.is_empty() {
+            return;
+        }
+        if let Some(idx) = self.files.iter().position(|f| f.path() == std::path::Path::new(path)) {
+            self.set_file_idx(idx);
+        } else {
+            match OpenFile::open_or_new(path) {
+                Ok(file) => self.add_file(file),
+                Err(e) => self.messages.error(format!("Could not open {}", e)),
+            }
+        }
+    }
+
+    /// Selects the next occurrence of `needle` after the primary cursor.
+    fn search(&mut self, needle: &str) {
+        if needle.is_empty() {
+            return;
+        }
+        let file = self.curr_file();
+        let from = self.selections.primary().range(file).end;
+        match file.buffer().find(needle, from) {
+            Some(at) => {
+                let mut sel = Selection::default();
+                sel.set_range(file, at..at + needle.chars().count());
+                self.selections.set(sel);
+            },
+            None => self.messages.warn(format!("Pattern not found: {}", needle)),
+        }
+    }
+
+    /// Whether the user quit, as opposed to the editor stopping some other way.
+    pub fn has_quit(&self) -> bool {
+        self.quit
+    }
+
+    fn quit(&mut self) -> TermResult<()> {
+        self.quit = true;
+        self.term.ex(TermOp::Exit)
+    }
+
+    /// Opens the message history in a scratch buffer.
+    fn show_messages(&mut self) {
+        let text = self.messages.to_text();
+        self.show_scratch("[messages]", &text);
+    }
+
+    /// Opens `text` in a scratch buffer, replacing an older one of the same name.
+    fn show_scratch(&mut self, name: &str, text: &str) {
+        if let Some(idx) = self.files.iter().position(|f| f.is_scratch() && f.path().as_os_str() == name) {
+            self.del_file(idx);
+        }
+        self.add_file(OpenFile::scratch(name, text));
+        self.selections = Selections::default();
+    }
+
     fn scroll(&mut self) {
-        let curr_pos = &self.cursor;
-        let (w, h): (usize, usize) = (self.term.dims.x, self.term.dims.y);
-        let mut offset = &mut self.offset;
+        let curr_pos = Coords { x: self.cursor_x(), y: self.cursor().line };
+        let (w, h): (usize, usize) = (self.text_width(), self.text_height());
+        let offset = &mut self.offset;
         if curr_pos.y < offset.y {
             offset.y = curr_pos.y;
         } else if curr_pos.y >= offset.y.saturating_add(h) {
@@ -118,70 +962,750 @@ impl Editor {
         }
     }
 
-    fn welcome(&self) {
+    fn welcome(&self, frame: &mut Frame, y: usize) {
         let vers: &str = env!("CARGO_PKG_VERSION");
-        let msg = format!("Div {}\r", vers);
-        let width = std::cmp::min(self.term.dims.y as usize, msg.len());
-        println!("{}\r", &msg[..width])
+        let msg = format!("Div {}", vers);
+        let x = self.term.dims.x.saturating_sub(msg.len()) / 2;
+        frame.print(0, y, "~", Style::default());
+        frame.print(x.max(1), y, &msg, Style::default());
+    }
 
+    /// Open files with unsaved changes, scratch buffers left out.
+    pub fn dirty_files(&self) -> impl Iterator<Item = &OpenFile> {
+        self.files.iter().filter(|f| f.is_dirty() && !f.is_scratch())
     }
 
-    fn add_file(&mut self, file: OpenFile) {
+    pub fn add_file(&mut self, mut file: OpenFile) {
+        file.set_undo_file(self.config.undo_file);
+        if !file.is_scratch() && !file.path().as_os_str().is_empty() {
+            self.watcher.watch(file.path());
+        }
         self.files.push(file);
-        self.file_idx = self.files.len();
+        self.set_file_idx(self.files.len() - 1);
+    }
+
+    pub fn del_file(&mut self, file_idx: usize) {
+        let file = self.files.remove(file_idx);
+        if !self.files.iter().any(|f| f.path() == file.path()) {
+            self.watcher.unwatch(file.path());
+        }
+        if self.files.is_empty() {
+            self.files.push(OpenFile::default());
+        }
+        if file_idx == self.file_idx {
+            self.file_idx = file_idx.min(self.files.len() - 1);
+            self.selections = self.curr_file_mut().take_selections();
+        } else if file_idx < self.file_idx {
+            self.file_idx -= 1;
+        }
+    }
+
+    /// Makes the file at `idx` the current one. The file left keeps its selections, and
+    /// the one switched to gets back its own.
+    fn set_file_idx(&mut self, idx: usize) {
+        let selections = std::mem::take(&mut self.selections);
+        self.curr_file_mut().stash_selections(selections);
+        self.file_idx = idx;
+        self.selections = self.curr_file_mut().take_selections();
+    }
+
+    /// Interprets `action` in the current mode. Returns the action to run as usual, or
+    /// `None` if the mode took care of it.
+    fn modal_action(&mut self, action: Action) -> TermResult<Option<Action>> {
+        use action::{Direction::*, Location};
+        if !self.config.modal {
+            return Ok(Some(action));
+        }
+        match (self.mode, action) {
+            (_, Action::Count(0)) if self.count.is_none() => {
+                return self.modal_action(Action::Move(To(Location::Index(0))));
+            },
+            (_, Action::Count(n)) => {
+                self.count = Some(self.count.unwrap_or(0).saturating_mul(10).saturating_add(n as usize));
+            },
+            (_, Action::SetMode(mode)) => self.set_mode(mode),
+            (Mode::Normal, Action::Operator(op)) => {
+                self.op_count = self.count.take().unwrap_or(1);
+                self.mode = Mode::Pending(op);
+            },
+            (Mode::Visual(visual), Action::Operator(op)) => {
+                let ranges = self.selections.iter().map(|sel| self.selection_range(sel)).collect();
+                self.operate(op, ranges, visual == Visual::Line);
+            },
+            // Doubled operators, like `dd`, work on whole lines.
+            (Mode::Pending(pending), Action::Operator(op)) if pending == op => {
+                let n = self.take_count();
+                let buffer = self.curr_file().buffer();
+                let last = buffer.len_lines().saturating_sub(1);
+                let ranges = self.selections.iter()
+                    .map(|sel| line_span(buffer, sel.head.line, (sel.head.line + n - 1).min(last)))
+                    .collect();
+                self.operate(op, ranges, true);
+            },
+            (Mode::Pending(op), Action::Move(dir)) => {
+                let n = self.take_count();
+                let linewise = matches!(&dir, Up(_) | Down(_)
+                    | To(Location::Beginning(Target::Document)) | To(Location::End(Target::Document)));
+                for sel in self.selections.iter_mut() {
+                    sel.collapse();
+                }
+                match dir {
+                    Left(_) | Right(_) => self.line_motion(&dir, n, true),
+                    _ => for _ in 0..n {
+                        self.extend_motion(&dir);
+                    },
+                }
+                let file = self.curr_file();
+                let ranges = self.selections.iter()
+                    .map(|sel| if linewise { whole_lines(file.buffer(), sel.range(file)) } else { sel.range(file) })
+                    .collect();
+                self.operate(op, ranges, linewise);
+            },
+            (Mode::Pending(op), Action::Select(dir)) => {
+                self.take_count();
+                self.motion(&dir, true);
+                let file = self.curr_file();
+                let ranges = self.selections.iter().map(|sel| sel.range(file)).collect();
+                self.operate(op, ranges, false);
+            },
+            (Mode::Pending(_), _) => self.set_mode(Mode::Normal),
+            // Sideways motion and deletion stay on the line.
+            (Mode::Normal, Action::Move(dir @ (Left(_) | Right(_)))) => {
+                self.line_motion(&dir, 1, false);
+                self.collapse_selections();
+            },
+            (Mode::Normal, Action::Delete(dir @ (Left(_) | Right(_)))) => {
+                self.collapse_selections();
+                self.line_motion(&dir, 1, true);
+                let file = self.curr_file();
+                let ranges = self.selections.iter().map(|sel| sel.range(file)).collect();
+                self.operate(Operator::Delete, ranges, false);
+            },
+            (Mode::Visual(Visual::Block), Action::Move(dir)) => self.block_motion(&dir),
+            (Mode::Visual(_), Action::Move(dir @ (Left(_) | Right(_)))) => {
+                self.line_motion(&dir, 1, false);
+            },
+            (Mode::Visual(_), Action::Move(dir)) => self.extend_motion(&dir),
+            (_, action) => return Ok(Some(action)),
+        }
+        Ok(None)
+    }
+
+    /// The chars `sel` covers. In visual mode that includes the char under the cursor, or
+    /// whole lines in visual line mode.
+    fn selection_range(&self, sel: &Selection) -> Range<usize> {
+        let file = self.curr_file();
+        let buffer = file.buffer();
+        let range = sel.range(file);
+        match self.mode() {
+            Mode::Visual(Visual::Line) => whole_lines(buffer, range),
+            Mode::Visual(_) if range.end < buffer.len_chars() && !matches!(buffer.char(range.end), '\n' | '\r') => {
+                range.start..range.end + 1
+            },
+            _ => range,
+        }
+    }
+
+    /// The count for an operator's motion: the one typed before the operator times the
+    /// one typed after it.
+    fn take_count(&mut self) -> usize {
+        let n = self.op_count.saturating_mul(self.count.take().unwrap_or(1));
+        self.op_count = 1;
+        n.max(1)
+    }
+
+    fn set_mode(&mut self, mode: Mode) {
+        self.count = None;
+        self.op_count = 1;
+        // Asking for the visual mode already on leaves it.
+        let mode = if mode == self.mode && matches!(mode, Mode::Visual(_)) { Mode::Normal } else { mode };
+        let file = &self.files[self.file_idx];
+        match (self.mode, mode) {
+            (Mode::Visual(Visual::Block), Mode::Visual(Visual::Block)) => {},
+            (from, Mode::Visual(Visual::Block)) => {
+                self.selections.keep_primary();
+                let primary = self.selections.primary();
+                let anchor = if let Mode::Visual(_) = from { primary.anchor.clone() } else { primary.head.clone() };
+                self.block = (anchor, primary.head.clone());
+                self.select_block();
+            },
+            (Mode::Visual(Visual::Block), Mode::Visual(_)) => {
+                let (anchor, head) = self.block.clone();
+                self.selections.set(Selection { anchor, head });
+            },
+            (Mode::Visual(_), Mode::Visual(_)) => {},
+            (_, Mode::Visual(_)) => self.collapse_selections(),
+            (Mode::Insert, Mode::Normal) => {
+                for sel in self.selections.iter_mut() {
+                    if sel.head.col > 0 {
+                        sel.head.left(file, 1);
+                    }
+                }
+                self.collapse_selections();
+            },
+            (Mode::Visual(Visual::Block), _) => self.selections.set(Selection::point(self.block.1.clone())),
+            (Mode::Visual(_), _) | (_, Mode::Insert) => self.collapse_selections(),
+            _ => {},
+        }
+        self.mode = mode;
+    }
+
+    fn collapse_selections(&mut self) {
+        for sel in self.selections.iter_mut() {
+            sel.collapse();
+        }
+        let file = &self.files[self.file_idx];
+        self.selections.normalize(file);
+    }
+
+    /// Moves the heads of the selections, leaving their anchors where they are.
+    fn extend_motion(&mut self, dir: &Direction) {
+        let anchors: Vec<Cursor> = self.selections.iter().map(|sel| sel.anchor.clone()).collect();
+        self.motion(dir, false);
+        if self.selections.len() == anchors.len() {
+            for (sel, anchor) in self.selections.iter_mut().zip(anchors) {
+                sel.anchor = anchor;
+            }
+            let file = &self.files[self.file_idx];
+            self.selections.normalize(file);
+        }
+    }
+
+    /// Moves the heads `times` times along their lines, without wrapping onto the next or
+    /// previous one. `past_end` lets them stop just after the last char, where a motion
+    /// for an operator ends.
+    fn line_motion(&mut self, dir: &Direction, times: usize, past_end: bool) {
+        let file = &self.files[self.file_idx];
+        for sel in self.selections.iter_mut() {
+            match *dir {
+                Direction::Left(n) => sel.head.left_in_line(times.saturating_mul(n as usize)),
+                Direction::Right(n) => sel.head.right_in_line(file, times.saturating_mul(n as usize), past_end),
+                _ => {},
+            }
+        }
+        self.selections.normalize(file);
+    }
+
+    /// Moves the corner of the visual block opposite its anchor.
+    fn block_motion(&mut self, dir: &Direction) {
+        self.selections.set(Selection::point(self.block.1.clone()));
+        self.motion(dir, false);
+        self.block.1 = self.cursor().clone();
+        self.select_block();
     }
 
-    fn del_file(&mut self, file_idx: usize) {
-        self.files.remove(file_idx);
-        if self.file_idx == self.files.len() + 1 {
-            self.file_idx = self.files.len();
+    /// Selects the display columns between the corners of the visual block, with one
+    /// selection per line. The one on the moving corner's line is the primary one.
+    fn select_block(&mut self) {
+        let tab_width = self.config.tab_width;
+        let file = &self.files[self.file_idx];
+        let (anchor, head) = &self.block;
+        let (from, to) = (anchor.display_col(file, tab_width), head.display_col(file, tab_width));
+        let mut lines: Vec<usize> = (anchor.line.min(head.line)..=anchor.line.max(head.line))
+            .filter(|line| *line != head.line)
+            .collect();
+        lines.push(head.line);
+        let mut selections = lines.into_iter().filter_map(|line| {
+            let row = file.get(line)?;
+            let (a, h) = (row.col_at(from, tab_width), row.col_at(to, tab_width));
+            Some(Selection { anchor: Cursor::new(line, a), head: Cursor::new(line, h) })
+        });
+        if let Some(first) = selections.next() {
+            self.selections.set(first);
+            for sel in selections {
+                self.selections.push(sel, file);
+            }
+        }
+    }
+
+    /// Applies `op` to a range per selection, then goes back to normal mode, or on to
+    /// insert mode after a change.
+    fn operate(&mut self, op: Operator, ranges: Vec<Range<usize>>, linewise: bool) {
+        self.mode = if op == Operator::Change { Mode::Insert } else { Mode::Normal };
+        self.count = None;
+        if ranges.iter().all(|r| r.is_empty()) && op != Operator::Change {
+            self.collapse_selections();
+            return;
+        }
+        let buffer = self.curr_file().buffer();
+        let yank = self.yank_ranges(&ranges.iter().cloned().map(Some).collect::<Vec<_>>());
+        let ranges: Vec<Option<Range<usize>>> = ranges.into_iter()
+            .map(|mut r| {
+                let ends_line = r.end > r.start && buffer.char(r.end - 1) == '\n';
+                match op {
+                    // Deleting the last lines takes the line break before them instead.
+                    Operator::Delete if linewise && !ends_line && r.end == buffer.len_chars() && r.start > 0 => {
+                        r.start -= if r.start > 1 && buffer.char(r.start - 2) == '\r' { 2 } else { 1 };
+                    },
+                    // Changing lines leaves an empty one to type in.
+                    Operator::Change if linewise && ends_line => {
+                        r.end -= if r.end - r.start > 1 && buffer.char(r.end - 2) == '\r' { 2 } else { 1 };
+                    },
+                    _ => {},
+                }
+                Some(r)
+            })
+            .collect();
+        match op {
+            Operator::Yank => {
+                if let Some(yank) = yank {
+                    self.store(yank, false);
+                }
+                let file = &self.files[self.file_idx];
+                for (sel, range) in self.selections.iter_mut().zip(&ranges) {
+                    if let Some(range) = range {
+                        sel.head.set(file.coords(range.start));
+                    }
+                    sel.collapse();
+                }
+                self.selections.normalize(file);
+            },
+            Operator::Delete | Operator::Change => {
+                if let Some(yank) = yank {
+                    self.store(yank, true);
+                }
+                self.edit_each_indexed(|file, i, range| match &ranges[i] {
+                    Some(r) => file.remove(r.clone()),
+                    None => range.start,
+                });
+            },
         }
     }
 
-    pub fn execute(action: Action) -> TermResult<()> {
+    pub fn execute(&mut self, action: Action) -> TermResult<()> {
+        if self.prompt.is_some() {
+            return self.prompt_action(action);
+        }
+        if let Some(action) = self.modal_action(action)? {
+            self.apply(action)?;
+        }
+        if matches!(self.mode(), Mode::Normal | Mode::Visual(Visual::Char) | Mode::Visual(Visual::Line)) {
+            let file = &self.files[self.file_idx];
+            for sel in self.selections.iter_mut() {
+                sel.head.clamp_to_line(file);
+            }
+            self.selections.normalize(file);
+        }
+        Ok(())
+    }
+
+    /// Runs an action the mode didn't take care of.
+    fn apply(&mut self, action: Action) -> TermResult<()> {
         use action::Direction::*;
-        let mut s = std::io::stdout();
-        let pos = Coords::from(cursor::position().unwrap());
+        if !matches!(action, Action::Input(_) | Action::Delete(_)) {
+            self.curr_file_mut().seal_history();
+        }
+        let last_paste = self.last_paste.take();
         match action {
-            Action::Move(loc) => match loc {
-                Up(n) => execute!(s, MoveUp(n))?,
-                Down(n) => execute!(s, MoveUp(n))?,
-                Left(n) => execute!(s, MoveUp(n))?,
-                Right(n) => execute!(s, MoveRight(n))?,
-                ToIdx(loc) => execute!(s, MoveTo(loc.0, loc.1))?,
+            Action::Input(text) => self.edit_each(|file, range| {
+                let at = file.remove(range);
+                file.insert(at, &text)
+            }),
+            Action::Delete(dir) => match dir {
+                Left(n) => self.edit_each(|file, range| if range.is_empty() {
+                    file.delete_back(range.start, n as usize)
+                } else {
+                    file.remove(range)
+                }),
+                Right(n) => self.edit_each(|file, range| if range.is_empty() {
+                    file.delete_forward(range.start, n as usize)
+                } else {
+                    file.remove(range)
+                }),
+                Over(target) => {
+                    let ranges = self.ranges_for(|buffer, range| resolve_target(buffer, &target, range));
+                    self.kill_ranges(&ranges);
+                },
+                To(location) => {
+                    let ranges = self.ranges_for(|buffer, range| {
+                        motion::to(buffer, &location, range.start)
+                            .map(|to| to.min(range.start)..to.max(range.end))
+                    });
+                    self.kill_ranges(&ranges);
+                },
+                _ => {},
+            },
+            Action::Newline(dir) => match dir {
+                Left(_) | Right(_) => self.edit_each(|file, range| {
+                    let at = file.remove(range);
+                    file.split_line(at)
+                }),
+                Down(n) => self.edit_each(|file, range| {
+                    let line = file.coords(range.end).y;
+                    (line..line + n as usize).fold(range.end, |_, l| file.open_line(l, true))
+                }),
+                Up(n) => self.edit_each(|file, range| {
+                    let line = file.coords(range.start).y;
+                    (0..n).fold(range.start, |_, _| file.open_line(line, false))
+                }),
+                _ => {},
+            },
+            Action::MoveText(target, dir) => self.edit_each(|file, range| {
+                let head = range.start;
+                if let (Target::Line, Up(n)) | (Target::Line, Down(n)) = (&target, &dir) {
+                    let (first, last) = (file.coords(range.start).y, file.coords(range.end).y);
+                    let by = if let Up(_) = dir { -(*n as isize) } else { *n as isize };
+                    let col = head - file.buffer().line_to_char(first);
+                    return file.move_lines(first, last, by) + col;
+                }
+                let by = match dir {
+                    Left(n) => -(n as isize),
+                    Right(n) => n as isize,
+                    _ => return head,
+                };
+                match textobj::resolve(file.buffer(), &target, range) {
+                    Some(object) => file.move_range(object, by),
+                    None => head,
+                }
+            }),
+            Action::Move(dir) => self.motion(&dir, false),
+            Action::Select(dir) => self.motion(&dir, true),
+            Action::Scroll(dir) => {
+                let last = self.curr_file().len().saturating_sub(1);
+                let offset = &mut self.offset;
+                match dir {
+                    Up(n) => offset.y = offset.y.saturating_sub(n as usize),
+                    Down(n) => offset.y = offset.y.saturating_add(n as usize).min(last),
+                    Left(n) => offset.x = offset.x.saturating_sub(n as usize),
+                    Right(n) => offset.x = offset.x.saturating_add(n as usize),
+                    _ => {},
+                }
+                if matches!(dir, Up(_) | Down(_)) {
+                    self.motion(&dir, false);
+                }
+            },
+            Action::Copy(target) => {
+                let ranges = self.ranges_for(|buffer, range| resolve_target(buffer, &target, range));
+                if let Some(yank) = self.yank_ranges(&ranges) {
+                    self.store(yank, false);
+                }
+            },
+            Action::Paste => if let Some(yank) = self.take_paste() {
+                self.paste(yank);
+            },
+            Action::PasteCycle => if let Some((ranges, linewise)) = last_paste {
+                if let Some(yank) = self.registers.cycle() {
+                    self.unpaste(&ranges, linewise);
+                    self.paste(yank);
+                }
+            },
+            Action::SelectRegister(name) => self.registers.select(name),
+            Action::SetClipboard(provider) => self.clipboard.set_provider(provider),
+            Action::AddCursor(target) => match target {
+                Target::Word => self.add_cursor_on_next_match(),
+                Target::Line => self.split_selections_into_lines(),
                 _ => {},
             },
-            Action::Quit => Term::ex(crate::term::TermOp::Exit)?,
+            Action::ShowMessages => self.show_messages(),
+            Action::ClearSelections => {
+                self.messages.dismiss();
+                self.selections.keep_primary();
+                self.selections.primary_mut().collapse();
+            },
+            Action::Undo => if let Some(to) = self.curr_file_mut().undo() {
+                self.selections.set(Selection::point(Cursor::new(to.y, to.x)));
+            },
+            Action::Redo => if let Some(to) = self.curr_file_mut().redo() {
+                self.selections.set(Selection::point(Cursor::new(to.y, to.x)));
+            },
+            Action::Quit => if self.files.iter().any(OpenFile::is_dirty) {
+                let prompt = Prompt::confirm("There are unsaved changes. Quit anyway?");
+                self.open_prompt(prompt, |editor, _| editor.quit());
+            } else {
+                self.quit()?
+            },
+            Action::Prompt(kind) => self.open_kind(kind),
+            Action::Command(line) => self.run_command(line)?,
+            Action::SwitchBuffer(to) | Action::SwitchTab(to) => self.switch_file(to),
+            Action::OpenFile(path) => self.open_path(&path.to_string_lossy()),
+            Action::DelFile(idx) if idx < self.files.len() => self.del_file(idx),
+            Action::Append => {
+                self.line_motion(&Right(1), 1, true);
+                self.collapse_selections();
+                self.set_mode(Mode::Insert);
+            },
+            Action::Record(register) => self.record(register),
+            Action::Replay(register) => self.replay_macro(register)?,
             _ => {},
         }
-        return Ok(());
+        Ok(())
     }
 
-}
+    /// Applies an edit at every selection as a single undo step. `edit` gets the
+    /// selection's char range and returns where that selection's cursor ends up.
+    fn edit_each<F>(&mut self, mut edit: F)
+    where F: FnMut(&mut OpenFile, Range<usize>) -> usize
+    {
+        self.edit_each_indexed(|file, _, range| edit(file, range))
+    }
+
+    fn edit_each_indexed<F>(&mut self, mut edit: F)
+    where F: FnMut(&mut OpenFile, usize, Range<usize>) -> usize
+    {
+        let file = &mut self.files[self.file_idx];
+        let ranges: Vec<Range<usize>> = self.selections.iter().map(|s| s.range(file)).collect();
+        let mut heads = vec![0; ranges.len()];
+        file.begin_batch();
+        for (i, range) in ranges.into_iter().enumerate().rev() {
+            let before = file.buffer().len_chars() as isize;
+            heads[i] = edit(file, i, range);
+            let delta = file.buffer().len_chars() as isize - before;
+            for head in heads[i + 1..].iter_mut() {
+                *head = (*head as isize + delta) as usize;
+            }
+        }
+        file.end_batch();
+        for (sel, head) in self.selections.iter_mut().zip(heads) {
+            sel.head.set(file.coords(head));
+            sel.collapse();
+        }
+        self.selections.normalize(file);
+    }
+
+    /// Resolves a range for every selection, in selection order.
+    fn ranges_for<F>(&self, resolve: F) -> Vec<Option<Range<usize>>>
+    where F: Fn(&Buffer, Range<usize>) -> Option<Range<usize>>
+    {
+        let file = self.curr_file();
+        self.selections.iter().map(|sel| resolve(file.buffer(), sel.range(file))).collect()
+    }
+
+    fn yank_ranges(&self, ranges: &[Option<Range<usize>>]) -> Option<Yank> {
+        let buffer = self.curr_file().buffer();
+        let ranges: Vec<&Range<usize>> = ranges.iter().flatten().filter(|r| !r.is_empty()).collect();
+        if ranges.is_empty() {
+            return None;
+        }
+        let linewise = ranges.iter().all(|r| {
+            r.start == buffer.line_to_char(buffer.char_to_line(r.start))
+                && (r.end == buffer.len_chars() || buffer.line_to_char(buffer.char_to_line(r.end)) == r.end)
+                && buffer.char_to_line(r.end) > buffer.char_to_line(r.start)
+        });
+        let texts = ranges.iter().map(|r| buffer.slice((*r).clone()).to_string()).collect();
+        Some(Yank::new(texts, linewise))
+    }
+
+    /// Deletes a range at every selection, putting the text in the registers.
+    fn kill_ranges(&mut self, ranges: &[Option<Range<usize>>]) {
+        if let Some(yank) = self.yank_ranges(ranges) {
+            self.store(yank, true);
+        }
+        self.edit_each_indexed(|file, i, range| match &ranges[i] {
+            Some(r) => file.remove(r.clone()),
+            None => range.start,
+        });
+    }
 
-impl Default for Editor {
-    fn default() -> Self {
-        let args: Vec<String> = env::args().collect();
-        let mut files = Vec::new();
-        if args.len() > 1 {
-            for arg in args {
-                files.push(OpenFile::new(&arg).unwrap_or_default());
+    fn uses_clipboard(&self) -> bool {
+        self.registers.selected().map_or(self.config.clipboard_sync, Registers::is_clipboard)
+    }
+
+    /// Puts copied or deleted text in the registers, and on the system clipboard when the
+    /// clipboard register is selected or clipboard sync is on.
+    fn store(&mut self, yank: Yank, kill: bool) {
+        if self.uses_clipboard() {
+            if let Err(e) = self.clipboard.copy(&mut self.term, &yank.joined()) {
+                self.messages.error(format!("Could not copy to the clipboard: {}", e));
             }
+        }
+        if kill {
+            self.registers.kill(yank);
         } else {
-            files = vec![OpenFile::default()]
-        };
-        let term = Term::default();
-        term.init().expect("Could not init");
-        Self {
-            quit: false,
-            files, term,
-            ..Default::default()
+            self.registers.yank(yank);
         }
     }
+
+    /// The text the next paste inserts. When it comes from the system clipboard, the
+    /// register is kept if it still holds the same text, so multi-cursor and linewise
+    /// pastes survive a round trip; otherwise text ending in a newline pastes linewise.
+    fn take_paste(&mut self) -> Option<Yank> {
+        if self.uses_clipboard() {
+            let name = self.registers.selected().unwrap_or(Registers::UNNAMED);
+            match self.clipboard.paste(&mut self.term, &mut self.events) {
+                Ok(Some(text)) => if self.registers.get(name).map(Yank::joined).as_ref() != Some(&text) {
+                    let linewise = text.ends_with('\n');
+                    self.registers.set(name, Yank::new(vec![text], linewise));
+                },
+                Ok(None) => {},
+                Err(e) => self.messages.error(format!("Could not paste from the clipboard: {}", e)),
+            }
+        }
+        self.registers.paste()
+    }
+
+    /// Pastes one text per selection if the yank has as many, otherwise all of it at each.
+    /// Linewise text goes below the selection's line rather than at the cursor.
+    fn paste(&mut self, yank: Yank) {
+        let n = self.selections.len();
+        let texts = if yank.texts.len() == n { yank.texts.clone() } else { vec![yank.joined(); n] };
+        let mut inserted = vec![0..0; n];
+        let mut deltas = vec![0; n];
+        self.edit_each_indexed(|file, i, range| {
+            let text = &texts[i];
+            let before = file.buffer().len_chars() as isize;
+            let head = if !yank.linewise {
+                let at = file.remove(range);
+                inserted[i] = at..file.insert(at, text);
+                inserted[i].end
+            } else if file.coords(range.end).y + 1 < file.len() {
+                let at = file.buffer().line_to_char(file.coords(range.end).y + 1);
+                inserted[i] = at..file.insert(at, text);
+                at
+            } else {
+                // The last line has no line ending to paste after, so one goes in front.
+                let line_ending = file.line_ending();
+                let text = text.strip_suffix(line_ending).unwrap_or(text);
+                let at = file.buffer().len_chars();
+                inserted[i] = at..file.insert(at, &format!("{}{}", line_ending, text));
+                at + line_ending.chars().count()
+            };
+            deltas[i] = file.buffer().len_chars() as isize - before;
+            head
+        });
+        // Each paste moved the ones after it by what the ones before it added.
+        let mut shift = 0;
+        for (range, delta) in inserted.iter_mut().zip(deltas) {
+            *range = (range.start as isize + shift) as usize..(range.end as isize + shift) as usize;
+            shift += delta;
+        }
+        self.last_paste = Some((inserted, yank.linewise));
+    }
+
+    /// Takes out what the last paste inserted, leaving each cursor where pasting again
+    /// puts the text in the same place.
+    fn unpaste(&mut self, ranges: &[Range<usize>], linewise: bool) {
+        let file = &mut self.files[self.file_idx];
+        file.begin_batch();
+        for range in ranges.iter().rev() {
+            file.remove(range.clone());
+        }
+        file.end_batch();
+        let mut removed = 0;
+        let heads: Vec<Coords> = ranges.iter().map(|range| {
+            let start = range.start - removed;
+            removed += range.len();
+            let at = file.coords(start);
+            // A linewise paste goes below the cursor's line, so the cursor goes back to the
+            // line above. One at the end of the file went after the last line.
+            if linewise && start < file.buffer().len_chars() && at.y > 0 {
+                Coords { x: 0, y: at.y - 1 }
+            } else {
+                at
+            }
+        }).collect();
+        for (sel, head) in self.selections.iter_mut().zip(heads) {
+            sel.head.set(head);
+            sel.collapse();
+        }
+        self.selections.normalize(file);
+    }
+
+    fn motion(&mut self, dir: &Direction, extend: bool) {
+        use action::Direction::*;
+        let tab_width = self.config.tab_width;
+        let file = &self.files[self.file_idx];
+        if let ToIdx((col, line)) = dir {
+            self.selections.keep_primary();
+            self.selections.primary_mut().head.set(Coords::new(*col, *line));
+        }
+        for sel in self.selections.iter_mut() {
+            match dir {
+                Up(n) => sel.head.up(file, *n as usize, tab_width),
+                Down(n) => sel.head.down(file, *n as usize, tab_width),
+                Left(n) => sel.head.left(file, *n as usize),
+                Right(n) => sel.head.right(file, *n as usize),
+                To(location) => {
+                    let head = file.char_idx(&sel.head.coords());
+                    if let Some(to) = motion::to(file.buffer(), location, head) {
+                        sel.head.set(file.coords(to));
+                    }
+                },
+                Over(target) => match textobj::resolve(file.buffer(), target, sel.range(file)) {
+                    Some(object) if extend => sel.set_range(file, object),
+                    Some(object) => sel.head.set(file.coords(object.end)),
+                    None => {},
+                },
+                _ => {},
+            }
+            if !extend {
+                sel.collapse();
+            }
+        }
+        self.selections.normalize(file);
+    }
+
+    /// Selects the word under the primary cursor, or if something is already selected,
+    /// adds a selection on its next occurrence.
+    fn add_cursor_on_next_match(&mut self) {
+        let file = &self.files[self.file_idx];
+        let primary = self.selections.primary_mut();
+        let range = primary.range(file);
+        if range.is_empty() {
+            if let Some(word) = file.buffer().word_at(range.start) {
+                primary.set_range(file, word);
+            }
+            return;
+        }
+        let needle = file.buffer().slice(range.clone()).to_string();
+        let from = self.selections.iter().map(|s| s.range(file).end).max().unwrap_or(range.end);
+        if let Some(start) = file.buffer().find(&needle, from) {
+            let mut sel = Selection::default();
+            sel.set_range(file, start..start + needle.chars().count());
+            self.selections.push(sel, file);
+        }
+    }
+
+    /// Splits every multi-line selection into one selection per line.
+    fn split_selections_into_lines(&mut self) {
+        let file = &self.files[self.file_idx];
+        let mut split = Vec::new();
+        for sel in self.selections.iter() {
+            let range = sel.range(file);
+            let (first, last) = (file.coords(range.start).y, file.coords(range.end).y);
+            for line in first..=last {
+                let line_start = file.buffer().line_to_char(line);
+                let line_end = line_start + file.buffer().line_len(line);
+                let mut sel = Selection::default();
+                sel.set_range(file, range.start.max(line_start)..range.end.min(line_end));
+                split.push(sel);
+            }
+        }
+        let mut split = split.into_iter();
+        if let Some(first) = split.next() {
+            self.selections.set(first);
+            for sel in split {
+                self.selections.push(sel, file);
+            }
+        }
+    }
+
+}
+
+/// Like `textobj::resolve`, but an empty selection stands for its whole line, so copying
+/// or cutting with nothing selected takes the line.
+fn resolve_target(buffer: &Buffer, target: &Target, range: Range<usize>) -> Option<Range<usize>> {
+    match target {
+        Target::Selection if range.is_empty() => {
+            textobj::resolve(buffer, &Target::Around(Box::new(Target::Line)), range)
+        },
+        _ => textobj::resolve(buffer, target, range),
+    }
+}
+
+impl Editor {
+    /// Opens the files named on the command line, starting new ones for paths that don't
+    /// exist yet. Files that exist but can't be read are an error.
+    pub fn from_args() -> TermResult<Self> {
+        let files = env::args()
+            .skip(1)
+            .map(OpenFile::open_or_new)
+            .collect::<io::Result<Vec<_>>>()?;
+        Self::with_user_config(Term::default(), files)
+    }
 }
 
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub struct Coords {
     pub x: usize, pub y: usize
 }
@@ -211,10 +1735,19 @@ pub enum Dir {
 
 impl Dir {
 
-    pub fn go(self, term: Term, amount: usize) -> TermResult<()> {
-        Term::ex(TermOp::Move(self, amount as u16))?;
+    pub fn go(self, term: &mut Term, amount: usize) -> TermResult<()> {
+        term.ex(TermOp::Move(self, amount as u16))?;
         Ok(())
     }
 }
 
+/// The chars of lines `first` to `last`, with their line breaks.
+fn line_span(buffer: &Buffer, first: usize, last: usize) -> Range<usize> {
+    let end = if last + 1 < buffer.len_lines() { buffer.line_to_char(last + 1) } else { buffer.len_chars() };
+    buffer.line_to_char(first)..end
+}
 
+/// `range` grown to the whole lines it touches.
+fn whole_lines(buffer: &Buffer, range: Range<usize>) -> Range<usize> {
+    line_span(buffer, buffer.char_to_line(range.start), buffer.char_to_line(range.end))
+}
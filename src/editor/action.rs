@@ -1,18 +1,16 @@
-use std::io::{self, Read, Write};
-use crate::{
-    editor::{Coords, Dir},
-    term::Term,
-};
-use crossterm::{
-    execute, write_ansi_code, Result as TermResult,
-    cursor::{MoveTo, MoveUp, MoveDown, MoveLeft, MoveRight, self},
-};
-use std::convert::TryFrom;
+use std::{fmt, path::PathBuf, str::FromStr};
 use crossterm::{
     event::{KeyCode, KeyModifiers},
 };
+use crate::{
+    editor::{clipboard::Provider, keymap::KeyChord, mode::{Mode, Operator}},
+    prompt::PromptKind,
+};
 
-#[derive(Debug)]
+/// Everything the editor can be asked to do. Actions are also written as text, for
+/// keymaps and macros: a verb followed by its arguments, e.g. `move up 2`,
+/// `select over inner double_quotes`, `delete to end word` or `:write`.
+#[derive(Debug, Clone)]
 pub enum Action {
     Move(Direction),
     MoveText(Target, Direction),
@@ -24,15 +22,35 @@ pub enum Action {
     SwitchBuffer(RelativeLocation),
     SwitchTab(RelativeLocation),
     Newline(Direction),
-    OpenFile(std::fs::File),
-    DelFile(std::fs::File),
+    OpenFile(PathBuf),
+    DelFile(usize),
     Quit,
     Copy(Target),
     Paste,
-
+    PasteCycle,
+    SelectRegister(char),
+    SetClipboard(Provider),
+    ShowMessages,
+    Prompt(PromptKind),
+    Command(String),
+    Undo,
+    Redo,
+    AddCursor(Target),
+    ClearSelections,
+    SetMode(Mode),
+    /// Goes to insert mode after the char under the cursor.
+    Append,
+    /// Applies to the selection in visual mode, otherwise waits for a motion or text object.
+    Operator(Operator),
+    /// A digit of the count for the next action.
+    Count(u8),
+    /// Starts recording a macro into a register, or stops the recording in progress.
+    Record(Option<char>),
+    /// Replays the macro in a register, by default the last one recorded or replayed.
+    Replay(Option<char>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Direction {
     Up(u16),
     Down(u16),
@@ -40,13 +58,15 @@ pub enum Direction {
     Right(u16),
     ToIdx((u16, u16)),
     To(Location),
+    Over(Target),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Target {
     Word,
     Document,
     Line,
+    Paragraph,
     Bracketed,
     Parenthesis,
     SingleQuotes,
@@ -56,110 +76,312 @@ pub enum Target {
     Char(char),
     InputWord(String),
     Region(u32, u32),
+    Inner(Box<Target>),
+    Around(Box<Target>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Location {
     End(Target),
     Beginning(Target),
+    /// The start of the next one.
+    Next(Target),
     Index(u8),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RelativeLocation {
     Next,
     Previous,
     DiffIndex(i8),
 }
 
-impl Action {
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(line) = s.strip_prefix(':') {
+            return Ok(Action::Command(line.to_string()));
+        }
+        let mut words = Words(s);
+        let verb = words.next()?;
+        let action = match verb {
+            "move" => Action::Move(words.direction()?),
+            "select" => Action::Select(words.direction()?),
+            "delete" => Action::Delete(words.direction()?),
+            "scroll" => Action::Scroll(words.direction()?),
+            "newline" => Action::Newline(words.direction()?),
+            "move_text" => Action::MoveText(words.target()?, words.direction()?),
+            "input" => Action::Input(unescape(words.rest())),
+            "copy" => Action::Copy(words.target()?),
+            "add_cursor" => Action::AddCursor(words.target()?),
+            "register" => Action::SelectRegister(words.char()?),
+            "clipboard" => Action::SetClipboard(words.rest().parse()?),
+            "prompt" => Action::Prompt(match words.next()? {
+                "command" => PromptKind::Command,
+                "path" => PromptKind::Path,
+                "search" => PromptKind::Search,
+                other => return Err(format!("unknown prompt: {}", other)),
+            }),
+            "command" => Action::Command(words.rest().to_string()),
+            "buffer" => Action::SwitchBuffer(words.relative()?),
+            "tab" => Action::SwitchTab(words.relative()?),
+            "open" => Action::OpenFile(words.rest().into()),
+            "close" => Action::DelFile(words.number()?),
+            "mode" => Action::SetMode(words.next()?.parse()?),
+            "append" => Action::Append,
+            "operator" => Action::Operator(words.next()?.parse()?),
+            "count" => Action::Count(words.number()?),
+            "record" => Action::Record(words.maybe_char()?),
+            "replay" => Action::Replay(words.maybe_char()?),
+            "paste" => Action::Paste,
+            "paste_cycle" => Action::PasteCycle,
+            "undo" => Action::Undo,
+            "redo" => Action::Redo,
+            "quit" => Action::Quit,
+            "clear_selections" => Action::ClearSelections,
+            "messages" => Action::ShowMessages,
+            "none" => Action::Unregistered((KeyCode::Null, KeyModifiers::NONE)),
+            other => return Err(format!("unknown action: {}", other)),
+        };
+        words.end()?;
+        Ok(action)
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Move(dir) => write!(f, "move {}", dir),
+            Action::Select(dir) => write!(f, "select {}", dir),
+            Action::Delete(dir) => write!(f, "delete {}", dir),
+            Action::Scroll(dir) => write!(f, "scroll {}", dir),
+            Action::Newline(dir) => write!(f, "newline {}", dir),
+            Action::MoveText(target, dir) => write!(f, "move_text {} {}", target, dir),
+            Action::Input(text) => write!(f, "input {}", escape(text)),
+            Action::Copy(target) => write!(f, "copy {}", target),
+            Action::AddCursor(target) => write!(f, "add_cursor {}", target),
+            Action::SelectRegister(c) => write!(f, "register {}", c),
+            Action::SetClipboard(provider) => write!(f, "clipboard {}", provider),
+            Action::Prompt(kind) => write!(f, "prompt {}", match kind {
+                PromptKind::Command => "command",
+                PromptKind::Path => "path",
+                PromptKind::Search => "search",
+                PromptKind::Confirm => "confirm",
+            }),
+            Action::Command(line) => write!(f, ":{}", line),
+            Action::SwitchBuffer(to) => write!(f, "buffer {}", to),
+            Action::SwitchTab(to) => write!(f, "tab {}", to),
+            Action::OpenFile(path) => write!(f, "open {}", path.display()),
+            Action::DelFile(idx) => write!(f, "close {}", idx),
+            Action::SetMode(mode) => write!(f, "mode {}", mode),
+            Action::Append => write!(f, "append"),
+            Action::Operator(op) => write!(f, "operator {}", op),
+            Action::Count(n) => write!(f, "count {}", n),
+            Action::Record(None) => write!(f, "record"),
+            Action::Record(Some(c)) => write!(f, "record {}", c),
+            Action::Replay(None) => write!(f, "replay"),
+            Action::Replay(Some(c)) => write!(f, "replay {}", c),
+            Action::Paste => write!(f, "paste"),
+            Action::PasteCycle => write!(f, "paste_cycle"),
+            Action::Undo => write!(f, "undo"),
+            Action::Redo => write!(f, "redo"),
+            Action::Quit => write!(f, "quit"),
+            Action::ClearSelections => write!(f, "clear_selections"),
+            Action::ShowMessages => write!(f, "messages"),
+            Action::Unregistered(_) => write!(f, "none"),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Up(n) => write!(f, "up {}", n),
+            Direction::Down(n) => write!(f, "down {}", n),
+            Direction::Left(n) => write!(f, "left {}", n),
+            Direction::Right(n) => write!(f, "right {}", n),
+            Direction::ToIdx((x, y)) => write!(f, "at {} {}", x, y),
+            Direction::To(Location::Beginning(target)) => write!(f, "to start {}", target),
+            Direction::To(Location::End(target)) => write!(f, "to end {}", target),
+            Direction::To(Location::Next(target)) => write!(f, "to next {}", target),
+            Direction::To(Location::Index(idx)) => write!(f, "to index {}", idx),
+            Direction::Over(target) => write!(f, "over {}", target),
+        }
+    }
+}
 
-    pub fn execute(self) -> TermResult<()> {
-        use Action::*;
-        use Location::*;
-        use Direction::*;
-        let mut s = std::io::stdout();
-        let pos = Coords::from(cursor::position().unwrap());
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Move(loc) => match loc {
-                Up(n) => execute!(s, MoveUp(n))?,
-                Down(n) => execute!(s, MoveUp(n))?,
-                Left(n) => execute!(s, MoveUp(n))?,
-                Right(n) => execute!(s, MoveRight(n))?,
-                ToIdx(loc) => execute!(s, MoveTo(loc.0, loc.1))?,
-                _ => {},
-            },
-            Quit => Term::ex(crate::term::TermOp::Exit)?,
-            _ => {},
+            Target::Word => write!(f, "word"),
+            Target::Document => write!(f, "document"),
+            Target::Line => write!(f, "line"),
+            Target::Paragraph => write!(f, "paragraph"),
+            Target::Bracketed => write!(f, "bracketed"),
+            Target::Parenthesis => write!(f, "parens"),
+            Target::SingleQuotes => write!(f, "single_quotes"),
+            Target::DoubleQuotes => write!(f, "double_quotes"),
+            Target::Backticks => write!(f, "backticks"),
+            Target::Selection => write!(f, "selection"),
+            Target::Char(' ') => write!(f, "char space"),
+            Target::Char(c) => write!(f, "char {}", c),
+            Target::InputWord(word) => write!(f, "text {}", escape(word)),
+            Target::Region(a, b) => write!(f, "region {} {}", a, b),
+            Target::Inner(target) => write!(f, "inner {}", target),
+            Target::Around(target) => write!(f, "around {}", target),
+        }
+    }
+}
+
+impl fmt::Display for RelativeLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelativeLocation::Next => write!(f, "next"),
+            RelativeLocation::Previous => write!(f, "prev"),
+            RelativeLocation::DiffIndex(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+/// The words of an action's text, parsed front to back.
+struct Words<'a>(&'a str);
+
+impl<'a> Words<'a> {
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let s = self.0.trim_start();
+        if s.is_empty() {
+            return Err("unexpected end of action".into());
+        }
+        let end = s.find(char::is_whitespace).unwrap_or(s.len());
+        self.0 = &s[end..];
+        Ok(&s[..end])
+    }
+
+    /// Everything left, for arguments that may contain spaces.
+    fn rest(&mut self) -> &'a str {
+        std::mem::take(&mut self.0).trim()
+    }
+
+    fn end(&self) -> Result<(), String> {
+        match self.0.trim() {
+            "" => Ok(()),
+            extra => Err(format!("unexpected {}", extra)),
+        }
+    }
+
+    fn number<N: FromStr>(&mut self) -> Result<N, String> {
+        let word = self.next()?;
+        word.parse().map_err(|_| format!("expected a number, not {}", word))
+    }
+
+    /// A count that defaults to 1 when the action ends here.
+    fn count(&mut self) -> Result<u16, String> {
+        if self.0.trim().is_empty() { Ok(1) } else { self.number() }
+    }
+
+    fn char(&mut self) -> Result<char, String> {
+        match self.next()? {
+            "space" => Ok(' '),
+            word if word.chars().count() == 1 => Ok(word.chars().next().unwrap_or_default()),
+            word => Err(format!("expected a single char, not {}", word)),
+        }
+    }
+
+    fn maybe_char(&mut self) -> Result<Option<char>, String> {
+        if self.0.trim().is_empty() { Ok(None) } else { self.char().map(Some) }
+    }
+
+    fn relative(&mut self) -> Result<RelativeLocation, String> {
+        match self.next()? {
+            "next" => Ok(RelativeLocation::Next),
+            "prev" | "previous" => Ok(RelativeLocation::Previous),
+            word => word.parse().map(RelativeLocation::DiffIndex)
+                .map_err(|_| format!("expected next, prev or an offset, not {}", word)),
         }
-        return Ok(());
+    }
+
+    fn direction(&mut self) -> Result<Direction, String> {
+        Ok(match self.next()? {
+            "up" => Direction::Up(self.count()?),
+            "down" => Direction::Down(self.count()?),
+            "left" => Direction::Left(self.count()?),
+            "right" => Direction::Right(self.count()?),
+            "at" => Direction::ToIdx((self.number()?, self.number()?)),
+            "over" => Direction::Over(self.target()?),
+            "to" => Direction::To(match self.next()? {
+                "start" | "beginning" => Location::Beginning(self.target()?),
+                "end" => Location::End(self.target()?),
+                "next" => Location::Next(self.target()?),
+                "index" => Location::Index(self.number()?),
+                word => return Err(format!("expected start, end, next or index, not {}", word)),
+            }),
+            word => return Err(format!("unknown direction: {}", word)),
+        })
+    }
+
+    fn target(&mut self) -> Result<Target, String> {
+        Ok(match self.next()? {
+            "word" => Target::Word,
+            "document" => Target::Document,
+            "line" => Target::Line,
+            "paragraph" => Target::Paragraph,
+            "bracketed" => Target::Bracketed,
+            "parens" => Target::Parenthesis,
+            "single_quotes" => Target::SingleQuotes,
+            "double_quotes" => Target::DoubleQuotes,
+            "backticks" => Target::Backticks,
+            "selection" => Target::Selection,
+            "char" => Target::Char(self.char()?),
+            "text" => Target::InputWord(unescape(self.next()?)),
+            "region" => Target::Region(self.number()?, self.number()?),
+            "inner" => Target::Inner(Box::new(self.target()?)),
+            "around" => Target::Around(Box::new(self.target()?)),
+            word => return Err(format!("unknown target: {}", word)),
+        })
     }
 }
 
-impl From<(KeyCode, KeyModifiers)> for Action {
-    fn from((key, kmod): (KeyCode, KeyModifiers)) -> Self {
-        use Direction::{Up, Down, Left, Right, To};
-        use Location::{End, Beginning};
-        use RelativeLocation::{Next, Previous};
-        use Target::{Line, Document};
-        if kmod.eq(&KeyModifiers::NONE) {
-            match key {
-                KeyCode::Up => return Self::Move(Up(1)),
-                KeyCode::Down => return Self::Move(Down(1)),
-                KeyCode::Right => return Self::Move(Right(1)),
-                KeyCode::Left => return Self::Move(Left(1)),
-                KeyCode::End => return Self::Move(To(End(Line))),
-                KeyCode::Home => return Self::Move(To(Beginning(Line))),
-                KeyCode::PageUp => return Self::Scroll(Up(5)),
-                KeyCode::PageDown => return Self::Scroll(Down(5)),
-                KeyCode::Delete => return Self::Delete(Left(1)),
-                KeyCode::Backspace => return Self::Delete(Left(1)),
-
-                KeyCode::Char(c) => return Self::Input(c.to_string()),
-
-                _ => return Self::Unregistered((key, kmod)),
-            }
-        } else if kmod.contains(KeyModifiers::CONTROL) {
-            if kmod.contains(KeyModifiers::SHIFT) {
-                match key {
-                    KeyCode::Tab => return Self::SwitchTab(Next),
-                    KeyCode::Enter => return Self::Newline(Up(1)),
-                    _ => return Self::Unregistered((key, kmod)),
-                }
-            } else if kmod.contains(KeyModifiers::ALT) {
-                match key {
-                    _ => return Self::Unregistered((key, kmod)),
-                }
-            } else {
-                match key {
-                    KeyCode::Char('j') => Self::Move(Down(1)),
-                    KeyCode::Char('k') => Self::Move(Up(1)),
-                    KeyCode::Char('h') => Self::Move(Left(1)),
-                    KeyCode::Char('l') => Self::Move(Right(1)),
-                    KeyCode::Char('q') => Self::Quit,
-                    KeyCode::Up => Self::Scroll(Up(5)),
-                    KeyCode::Down => Self::Scroll(Down(5)),
-                    KeyCode::Left => Self::Scroll(Left(5)),
-                    KeyCode::Right => Self::Scroll(Right(5)),
-                    KeyCode::Tab => return Self::SwitchTab(Previous),
-                    KeyCode::Enter => return Self::Newline(Down(1)),
-                    _ => return Self::Unregistered((key, kmod)),
-                }
-            }
-        } else if kmod.contains(KeyModifiers::ALT) {
-            match key {
-                _ => return Self::Unregistered((key, kmod)),
-            }
-        } else if kmod.contains(KeyModifiers::SHIFT) {
-            match key {
-                KeyCode::PageUp => Self::Move(To(Beginning(Document))),
-                KeyCode::PageDown => Self::Move(To(End(Document))),
-                KeyCode::Char(c) => Self::Input(c.to_string().to_uppercase()),
-                _ => return Self::Unregistered((key, kmod)),
-            }
-        } else {
-            return Self::Unregistered((key, kmod));
+/// Escapes text so it survives as one word: backslashes, blanks and line breaks.
+pub fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ' ' => out.push_str("\\s"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
         }
     }
+    out
 }
 
+pub fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+impl From<KeyChord> for Action {
+    fn from(chord: KeyChord) -> Self {
+        Action::Unregistered((chord.code, chord.modifiers))
+    }
+}
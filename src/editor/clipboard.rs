@@ -0,0 +1,167 @@
+use std::{
+    env, fmt, io::{self, Write}, path::Path, process::{Command, Stdio}, str::FromStr, time::Duration,
+};
+use crate::{event::channel::Events, term::Term};
+
+const OSC52_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Where the system clipboard is read from and written to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Provider {
+    /// Kept inside the editor only.
+    Internal,
+    /// OSC 52 escape sequences written through the terminal, for SSH and tmux sessions.
+    Osc52,
+    /// External programs that take the text on stdin and print it on stdout.
+    Command { copy: Vec<String>, paste: Vec<String> },
+}
+
+impl Provider {
+
+    /// Picks a provider for the current environment: OSC 52 over SSH, otherwise the
+    /// platform's clipboard tools if they're installed. Inside tmux without a display
+    /// that is OSC 52 too, since tmux's own buffers never reach the outer terminal.
+    pub fn detect() -> Self {
+        if env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some() {
+            return Self::Osc52;
+        }
+        let preset = if cfg!(target_os = "macos") {
+            "pbcopy"
+        } else if env::var_os("WAYLAND_DISPLAY").is_some() {
+            "wl-copy"
+        } else if env::var_os("DISPLAY").is_some() {
+            if on_path("xclip") { "xclip" } else { "xsel" }
+        } else {
+            return Self::Osc52;
+        };
+        match preset.parse() {
+            Ok(provider @ Self::Command { .. }) if provider.is_installed() => provider,
+            _ => Self::Osc52,
+        }
+    }
+
+    pub fn command(copy: &str, paste: &str) -> Self {
+        let words = |cmd: &str| cmd.split_whitespace().map(String::from).collect();
+        Self::Command { copy: words(copy), paste: words(paste) }
+    }
+
+    fn is_installed(&self) -> bool {
+        match self {
+            Self::Command { copy, .. } => copy.first().is_some_and(|c| on_path(c)),
+            _ => true,
+        }
+    }
+}
+
+impl FromStr for Provider {
+    type Err = String;
+
+    /// Parses `internal`, `osc52`, the name of a known clipboard tool, or a custom
+    /// `copy command | paste command` pair.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim() {
+            "internal" | "none" => Self::Internal,
+            "osc52" => Self::Osc52,
+            "pbcopy" | "pbpaste" => Self::command("pbcopy", "pbpaste"),
+            "wl-copy" | "wl-paste" | "wayland" => Self::command("wl-copy", "wl-paste --no-newline"),
+            "xclip" => Self::command("xclip -selection clipboard -i", "xclip -selection clipboard -o"),
+            "xsel" => Self::command("xsel --clipboard --input", "xsel --clipboard --output"),
+            "tmux" => Self::command("tmux load-buffer -", "tmux save-buffer -"),
+            custom => match custom.split_once('|') {
+                Some((copy, paste)) if !copy.trim().is_empty() => Self::command(copy, paste),
+                _ => return Err(format!("unknown clipboard provider: {}", custom)),
+            },
+        })
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Internal => write!(f, "internal"),
+            Self::Osc52 => write!(f, "osc52"),
+            Self::Command { copy, paste } => write!(f, "{} | {}", copy.join(" "), paste.join(" ")),
+        }
+    }
+}
+
+/// The system clipboard, through a provider that can be switched while running. The
+/// last copied text is kept as well, for providers that can't be read back.
+#[derive(Debug)]
+pub struct Clipboard {
+    provider: Provider,
+    contents: Option<String>,
+}
+
+impl Clipboard {
+
+    pub fn new(provider: Provider) -> Self {
+        Self { provider, contents: None }
+    }
+
+    pub fn provider(&self) -> &Provider {
+        &self.provider
+    }
+
+    pub fn set_provider(&mut self, provider: Provider) {
+        self.provider = provider;
+    }
+
+    pub fn copy(&mut self, term: &mut Term, text: &str) -> io::Result<()> {
+        self.contents = Some(text.to_string());
+        match &self.provider {
+            Provider::Internal => Ok(()),
+            Provider::Osc52 => term.set_clipboard(text).map_err(io::Error::other),
+            Provider::Command { copy, .. } => {
+                let mut child = spawn(copy)?.stdin(Stdio::piped()).stdout(Stdio::null()).spawn()?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(text.as_bytes())?;
+                }
+                let status = child.wait()?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(format!("{} exited with {}", copy[0], status)))
+                }
+            },
+        }
+    }
+
+    /// Reads the clipboard, falling back to the last copied text when the provider
+    /// can't be read.
+    pub fn paste(&mut self, term: &mut Term, events: &mut Events) -> io::Result<Option<String>> {
+        let read = match &self.provider {
+            Provider::Internal => None,
+            Provider::Osc52 => term.get_clipboard(events, OSC52_TIMEOUT).map_err(io::Error::other)?,
+            Provider::Command { paste, .. } if paste.is_empty() => None,
+            Provider::Command { paste, .. } => {
+                let output = spawn(paste)?.stdin(Stdio::null()).output()?;
+                if !output.status.success() {
+                    return Err(io::Error::other(format!("{} exited with {}", paste[0], output.status)));
+                }
+                Some(String::from_utf8_lossy(&output.stdout).into_owned())
+            },
+        };
+        Ok(read.or_else(|| self.contents.clone()))
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new(Provider::detect())
+    }
+}
+
+fn spawn(args: &[String]) -> io::Result<Command> {
+    let (program, args) = args.split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty clipboard command"))?;
+    let mut command = Command::new(program);
+    command.args(args).stderr(Stdio::null());
+    Ok(command)
+}
+
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|dir| Path::new(&dir).join(program).is_file())
+    })
+}
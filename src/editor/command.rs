@@ -0,0 +1,581 @@
+use std::{ops::Range, path::{Path, PathBuf}};
+use regex::RegexBuilder;
+use crate::editor::{
+    Editor, action::{Action, RelativeLocation}, cursor::Cursor, macros, register::Yank, selection::Selection,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    Path,
+    Number,
+    Word,
+    /// Everything left on the line.
+    Rest,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Param {
+    pub name: &'static str,
+    pub kind: ParamKind,
+    pub optional: bool,
+}
+
+const fn req(name: &'static str, kind: ParamKind) -> Param {
+    Param { name, kind, optional: false }
+}
+
+const fn opt(name: &'static str, kind: ParamKind) -> Param {
+    Param { name, kind, optional: true }
+}
+
+const MAYBE_PATH: &[Param] = &[opt("path", ParamKind::Path)];
+const PATH: &[Param] = &[req("path", ParamKind::Path)];
+const LINE: &[Param] = &[req("line", ParamKind::Number)];
+const OPTION: &[Param] = &[req("option[=value]", ParamKind::Rest)];
+const SUBSTITUTION: &[Param] = &[req("/pattern/replacement/flags", ParamKind::Rest)];
+const REGISTER: &[Param] = &[req("register", ParamKind::Word)];
+const REPLAY: &[Param] = &[req("register", ParamKind::Word), opt("count", ParamKind::Number)];
+const SHELL: &[Param] = &[req("command", ParamKind::Rest)];
+const NAMED_MACRO: &[Param] = &[req("name", ParamKind::Word), opt("register", ParamKind::Word)];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Path(PathBuf),
+    Number(usize),
+    Text(String),
+}
+
+/// A parsed command line: the lines it applies to, whether it ended in `!`, and its
+/// arguments in the order the command declares them.
+#[derive(Debug, Clone, Default)]
+pub struct Invocation {
+    pub range: Option<Range<usize>>,
+    pub bang: bool,
+    pub args: Vec<Arg>,
+}
+
+impl Invocation {
+
+    pub fn path(&self, idx: usize) -> Option<&Path> {
+        match self.args.get(idx) {
+            Some(Arg::Path(path)) => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn number(&self, idx: usize) -> Option<usize> {
+        match self.args.get(idx) {
+            Some(Arg::Number(n)) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self, idx: usize) -> Option<&str> {
+        match self.args.get(idx) {
+            Some(Arg::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+pub type Run = fn(&mut Editor, &Invocation) -> Result<(), String>;
+
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub params: &'static [Param],
+    pub range: bool,
+    pub help: &'static str,
+    pub run: Run,
+}
+
+impl Command {
+
+    pub fn usage(&self) -> String {
+        let mut usage = String::new();
+        if self.range {
+            usage.push_str("[range]");
+        }
+        usage.push_str(self.name);
+        for param in self.params {
+            if param.optional {
+                usage.push_str(&format!(" [{}]", param.name));
+            } else {
+                usage.push_str(&format!(" <{}>", param.name));
+            }
+        }
+        usage
+    }
+}
+
+/// Every command that can be typed at the `:` prompt or bound to a key. Ranges are line
+/// addresses in front of the name: `10`, `.`, `$`, offsets like `.+2`, `a,b` or `%`.
+#[derive(Debug)]
+pub struct Registry {
+    commands: Vec<Command>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let commands = vec![
+            Command { name: "write", aliases: &["w"], params: MAYBE_PATH, range: false,
+                help: "Save the file, or save it as path. ! overwrites.", run: write },
+            Command { name: "quit", aliases: &["q"], params: &[], range: false,
+                help: "Quit. ! discards unsaved changes.", run: quit },
+            Command { name: "write-quit", aliases: &["wq", "x"], params: &[], range: false,
+                help: "Save the file and quit.", run: write_quit },
+            Command { name: "edit", aliases: &["e", "open"], params: PATH, range: false,
+                help: "Open a file, or switch to it if it is open.", run: edit },
+            Command { name: "buffer-next", aliases: &["bn", "bnext"], params: &[], range: false,
+                help: "Switch to the next open file.", run: buffer_next },
+            Command { name: "buffer-prev", aliases: &["bp", "bprev"], params: &[], range: false,
+                help: "Switch to the previous open file.", run: buffer_prev },
+            Command { name: "buffer-close", aliases: &["bd", "bclose"], params: &[], range: false,
+                help: "Close the file. ! discards unsaved changes.", run: buffer_close },
+            Command { name: "goto", aliases: &["g"], params: LINE, range: false,
+                help: "Go to a line. A range on its own does the same.", run: goto },
+            Command { name: "set", aliases: &["se"], params: OPTION, range: false,
+                help: "Set an option, e.g. set tab_width=8 or set noundo_file.", run: set },
+            Command { name: "delete", aliases: &["d"], params: &[], range: true,
+                help: "Cut lines into the registers.", run: delete },
+            Command { name: "yank", aliases: &["y"], params: &[], range: true,
+                help: "Copy lines into the registers.", run: yank },
+            Command { name: "substitute", aliases: &["s"], params: SUBSTITUTION,
+                range: true,
+                help: "Replace regex matches on the lines; g replaces every match, i ignores case.",
+                run: substitute },
+            Command { name: "undo", aliases: &["u"], params: &[], range: false,
+                help: "Undo the last change.", run: |editor, _| editor.execute(Action::Undo).map_err(|e| e.to_string()) },
+            Command { name: "redo", aliases: &["red"], params: &[], range: false,
+                help: "Redo the last undone change.", run: |editor, _| editor.execute(Action::Redo).map_err(|e| e.to_string()) },
+            Command { name: "replay", aliases: &["norm"], params: REPLAY, range: true,
+                help: "Replay the macro in a register count times, or once from the start of each line.",
+                run: replay },
+            Command { name: "macro-edit", aliases: &["medit"], params: REGISTER, range: false,
+                help: "Edit the macro in a register, one action per line. Writing the buffer keeps it.",
+                run: macro_edit },
+            Command { name: "macro-save", aliases: &["msave"], params: NAMED_MACRO, range: false,
+                help: "Save the macro in a register, by default the last one, under a name.",
+                run: macro_save },
+            Command { name: "macro-load", aliases: &["mload"], params: NAMED_MACRO, range: false,
+                help: "Load a saved macro into a register, by default the last one.", run: macro_load },
+            Command { name: "macros", aliases: &[], params: &[], range: false,
+                help: "List the saved macros.", run: list_macros },
+            Command { name: "shell", aliases: &["sh", "run"], params: SHELL, range: false,
+                help: "Run a shell command in the background, its output going to [shell].",
+                run: |editor, inv| editor.run_shell(inv.text(0).unwrap_or_default()) },
+            Command { name: "messages", aliases: &["mes"], params: &[], range: false,
+                help: "Show every message posted this session.", run: |editor, _| {
+                    editor.show_messages();
+                    Ok(())
+                } },
+            Command { name: "help", aliases: &["h"], params: &[], range: false,
+                help: "List the commands.", run: |editor, _| {
+                    let help = editor.commands.help();
+                    editor.show_scratch("[help]", &help);
+                    Ok(())
+                } },
+        ];
+        Self { commands }
+    }
+}
+
+impl Registry {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a command, replacing any with the same name.
+    pub fn register(&mut self, command: Command) {
+        self.commands.retain(|c| c.name != command.name);
+        self.commands.push(command);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.iter().find(|c| c.name == name || c.aliases.contains(&name))
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.commands.iter().map(|c| c.name).collect()
+    }
+
+    pub fn help(&self) -> String {
+        self.commands.iter()
+            .map(|c| {
+                let aliases = if c.aliases.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", c.aliases.join(", "))
+                };
+                format!("{}{}\n    {}\n", c.usage(), aliases, c.help)
+            })
+            .collect()
+    }
+
+    /// Parses a command line, with `current` and `last` the 0-based cursor and last lines
+    /// that relative addresses are resolved against.
+    pub fn parse(&self, line: &str, current: usize, last: usize) -> Result<(Command, Invocation), String> {
+        let line = line.trim().trim_start_matches(':');
+        let (range, rest) = parse_range(line, current, last)?;
+        let rest = rest.trim_start();
+        let name_len = rest.find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(rest.len());
+        let (name, rest) = rest.split_at(name_len);
+        let (bang, rest) = match rest.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        if name.is_empty() {
+            return match range {
+                Some(range) if rest.trim().is_empty() => {
+                    let command = self.get("goto").ok_or("goto is not defined")?.clone();
+                    Ok((command, Invocation { range: None, bang, args: vec![Arg::Number(range.end)] }))
+                },
+                _ => Err(format!("Not an editor command: {}", line)),
+            };
+        }
+        let command = self.get(name).ok_or_else(|| format!("Not an editor command: {}", name))?.clone();
+        if range.is_some() && !command.range {
+            return Err(format!("{} doesn't take a range", command.name));
+        }
+        let args = parse_args(command.params, rest)?;
+        Ok((command, Invocation { range, bang, args }))
+    }
+}
+
+fn parse_args(params: &[Param], line: &str) -> Result<Vec<Arg>, String> {
+    let mut rest = line.trim();
+    let mut args = Vec::new();
+    for param in params {
+        let word = if param.kind == ParamKind::Rest {
+            std::mem::take(&mut rest)
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let (word, tail) = rest.split_at(end);
+            rest = tail.trim_start();
+            word
+        };
+        if word.is_empty() {
+            if param.optional {
+                break;
+            }
+            return Err(format!("Missing <{}>", param.name));
+        }
+        args.push(match param.kind {
+            ParamKind::Path => Arg::Path(word.into()),
+            ParamKind::Number => Arg::Number(word.parse()
+                .map_err(|_| format!("<{}> should be a number, not {}", param.name, word))?),
+            ParamKind::Word | ParamKind::Rest => Arg::Text(word.to_string()),
+        });
+    }
+    if !rest.is_empty() {
+        return Err(format!("Too many arguments: {}", rest));
+    }
+    Ok(args)
+}
+
+/// Parses the range in front of a command, as a 0-based range of lines.
+fn parse_range(line: &str, current: usize, last: usize) -> Result<(Option<Range<usize>>, &str), String> {
+    if let Some(rest) = line.strip_prefix('%') {
+        return Ok((Some(0..last + 1), rest));
+    }
+    let (first, rest) = match parse_address(line, current, last)? {
+        (Some(first), rest) => (first, rest),
+        (None, rest) => return Ok((None, rest)),
+    };
+    let (second, rest) = match rest.strip_prefix(',') {
+        Some(rest) => match parse_address(rest, current, last)? {
+            (Some(second), rest) => (second, rest),
+            (None, _) => return Err("Missing address after ,".into()),
+        },
+        None => (first, rest),
+    };
+    let (start, end) = (first.min(second).min(last), first.max(second).min(last));
+    Ok((Some(start..end + 1), rest))
+}
+
+fn parse_address(line: &str, current: usize, last: usize) -> Result<(Option<usize>, &str), String> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (mut addr, mut rest) = if let Some(rest) = line.strip_prefix('.') {
+        (current, rest)
+    } else if let Some(rest) = line.strip_prefix('$') {
+        (last, rest)
+    } else if line.starts_with(|c: char| c.is_ascii_digit()) {
+        let (n, rest) = line.split_at(digits(line));
+        (n.parse::<usize>().map_err(|e| e.to_string())?.saturating_sub(1), rest)
+    } else if line.starts_with(['+', '-']) {
+        (current, line)
+    } else {
+        return Ok((None, line));
+    };
+    while let Some(sign) = rest.chars().next().filter(|c| *c == '+' || *c == '-') {
+        let (n, tail) = rest[1..].split_at(digits(&rest[1..]));
+        let n = if n.is_empty() { 1 } else { n.parse::<usize>().map_err(|e| e.to_string())? };
+        addr = if sign == '+' { addr.saturating_add(n) } else { addr.saturating_sub(n) };
+        rest = tail;
+    }
+    Ok((Some(addr), rest))
+}
+
+fn write(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    if let Some(register) = macros::buffer_register(editor.curr_file().path()).filter(|_| inv.args.is_empty()) {
+        let text = editor.curr_file().buffer().to_string();
+        let actions = macros::parse(&text)?;
+        editor.registers.set(register, Yank::new(vec![macros::to_text(&actions)], true));
+        editor.messages.info(format!("Macro @{} set, {} actions", register, actions.len()));
+        return Ok(());
+    }
+    let file = editor.curr_file_mut();
+    match inv.path(0) {
+        Some(path) => file.save_as(path, inv.bang),
+        None if inv.bang => file.force_save(),
+        None => file.save(),
+    }.map_err(|e| e.to_string())?;
+    let msg = format!("\"{}\" {}L written", file.path().display(), file.len());
+    editor.messages.info(msg);
+    Ok(())
+}
+
+fn quit(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    if !inv.bang {
+        if let Some(file) = editor.files.iter().find(|f| f.is_dirty()) {
+            return Err(format!("No write since last change to {} (add ! to override)", file.path().display()));
+        }
+    }
+    editor.quit().map_err(|e| e.to_string())
+}
+
+fn write_quit(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    write(editor, &Invocation { args: Vec::new(), ..inv.clone() })?;
+    quit(editor, inv)
+}
+
+fn edit(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    let path = inv.path(0).map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+    editor.open_path(&path);
+    Ok(())
+}
+
+fn buffer_next(editor: &mut Editor, _: &Invocation) -> Result<(), String> {
+    editor.switch_file(RelativeLocation::Next);
+    Ok(())
+}
+
+fn buffer_prev(editor: &mut Editor, _: &Invocation) -> Result<(), String> {
+    editor.switch_file(RelativeLocation::Previous);
+    Ok(())
+}
+
+fn buffer_close(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    if !inv.bang && editor.curr_file().is_dirty() {
+        return Err("No write since last change (add ! to override)".into());
+    }
+    editor.del_file(editor.file_idx);
+    Ok(())
+}
+
+fn goto(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    let last = editor.curr_file().len().saturating_sub(1);
+    let line = inv.number(0).unwrap_or(1).saturating_sub(1).min(last);
+    editor.selections.set(Selection::point(Cursor::new(line, 0)));
+    Ok(())
+}
+
+fn set(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    let option = inv.text(0).unwrap_or_default();
+    let (name, value) = match option.split_once(['=', ' ']) {
+        Some((name, value)) => (name.trim(), Some(value.trim())),
+        None => (option, None),
+    };
+    editor.config.set(name, value)?;
+    if name == "clipboard" {
+        editor.clipboard.set_provider(editor.config.clipboard.clone());
+    }
+    Ok(())
+}
+
+/// Chars covered by whole `lines`, line endings included. When the range runs to the
+/// end of a file without a final line ending, the ending before it is taken instead.
+fn line_chars(editor: &Editor, lines: &Range<usize>) -> Range<usize> {
+    let buffer = editor.curr_file().buffer();
+    let start = buffer.line_to_char(lines.start);
+    if lines.end < buffer.len_lines() {
+        return start..buffer.line_to_char(lines.end);
+    }
+    let start = match lines.start.checked_sub(1) {
+        Some(prev) => buffer.line_to_char(prev) + buffer.line_len(prev),
+        None => start,
+    };
+    start..buffer.len_chars()
+}
+
+fn current_line(editor: &Editor, inv: &Invocation) -> Range<usize> {
+    inv.range.clone().unwrap_or_else(|| {
+        let line = editor.cursor().line;
+        line..line + 1
+    })
+}
+
+fn yank_lines(editor: &Editor, lines: &Range<usize>) -> Yank {
+    let buffer = editor.curr_file().buffer();
+    let start = buffer.line_to_char(lines.start);
+    let end = if lines.end < buffer.len_lines() { buffer.line_to_char(lines.end) } else { buffer.len_chars() };
+    let mut text = buffer.slice(start..end).to_string();
+    if !text.ends_with('\n') {
+        text.push_str(editor.curr_file().line_ending());
+    }
+    Yank::new(vec![text], true)
+}
+
+fn delete(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    let lines = current_line(editor, inv);
+    let yank = yank_lines(editor, &lines);
+    editor.store(yank, true);
+    let chars = line_chars(editor, &lines);
+    editor.curr_file_mut().remove(chars);
+    let line = lines.start.min(editor.curr_file().len().saturating_sub(1));
+    editor.selections.set(Selection::point(Cursor::new(line, 0)));
+    Ok(())
+}
+
+fn yank(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    let lines = current_line(editor, inv);
+    let yank = yank_lines(editor, &lines);
+    editor.store(yank, false);
+    editor.messages.info(format!("{} lines yanked", lines.len()));
+    Ok(())
+}
+
+/// Splits `/pattern/replacement/flags` on its first char, which may be escaped with `\`
+/// inside the pattern or replacement.
+fn split_substitution(spec: &str) -> Result<(String, String, String), String> {
+    let mut chars = spec.chars();
+    let delim = chars.next().filter(|c| !c.is_alphanumeric() && *c != '\\')
+        .ok_or("Expected /pattern/replacement/")?;
+    let mut parts = vec![String::new()];
+    let mut escaped = false;
+    for c in chars {
+        if escaped {
+            if c != delim {
+                parts.last_mut().unwrap().push('\\');
+            }
+            parts.last_mut().unwrap().push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == delim && parts.len() < 3 {
+            parts.push(String::new());
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+    let mut parts = parts.into_iter();
+    let pattern = parts.next().unwrap_or_default();
+    let replacement = parts.next().unwrap_or_default();
+    let flags = parts.next().unwrap_or_default();
+    // Groups may be written vim-style as \1, or as $1.
+    let replacement = (0..10).fold(replacement, |r, n| r.replace(&format!("\\{}", n), &format!("${{{}}}", n)));
+    Ok((pattern, replacement, flags))
+}
+
+fn substitute(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    let (pattern, replacement, flags) = split_substitution(inv.text(0).unwrap_or_default())?;
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(flags.contains('i'))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let lines = current_line(editor, inv);
+    let buffer = editor.curr_file().buffer();
+    let start = buffer.line_to_char(lines.start);
+    let end = if lines.end < buffer.len_lines() { buffer.line_to_char(lines.end) } else { buffer.len_chars() };
+    let text = buffer.slice(start..end).to_string();
+    let (mut count, mut changed) = (0, 0);
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\n', '\r']);
+        let matches = regex.find_iter(body).count();
+        if matches == 0 {
+            out.push_str(line);
+            continue;
+        }
+        let limit = if flags.contains('g') { 0 } else { 1 };
+        out.push_str(&regex.replacen(body, limit, replacement.as_str()));
+        out.push_str(&line[body.len()..]);
+        count += if limit == 0 { matches } else { 1 };
+        changed += 1;
+    }
+    if count == 0 {
+        return Err(format!("Pattern not found: {}", pattern));
+    }
+    let file = editor.curr_file_mut();
+    file.begin_batch();
+    file.remove(start..end);
+    file.insert(start, &out);
+    file.end_batch();
+    editor.selections.set(Selection::point(Cursor::new(lines.start, 0)));
+    editor.messages.info(format!("{} substitutions on {} lines", count, changed));
+    Ok(())
+}
+
+fn register(inv: &Invocation, idx: usize) -> Result<Option<char>, String> {
+    match inv.text(idx) {
+        Some(name) if name.chars().count() == 1 && name.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            Ok(name.chars().next())
+        },
+        Some(name) => Err(format!("Not a macro register: {}", name)),
+        None => Ok(None),
+    }
+}
+
+fn replay(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    let register = register(inv, 0)?.unwrap_or_default();
+    let actions = editor.macro_actions(register).ok_or_else(|| format!("No macro in register {}", register))?;
+    match &inv.range {
+        Some(lines) => editor.replay_lines(&actions, lines.clone()),
+        None => (0..inv.number(1).unwrap_or(1)).try_for_each(|_| editor.run_macro(&actions)),
+    }.map_err(|e| e.to_string())
+}
+
+fn macro_edit(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    let register = register(inv, 0)?.unwrap_or_default().to_ascii_lowercase();
+    let text = editor.registers.get(register).map(Yank::joined).unwrap_or_default();
+    let text = macros::to_text(&macros::parse(&text)?);
+    editor.show_scratch(&macros::buffer_name(register), &text);
+    Ok(())
+}
+
+fn macro_save(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    let name = inv.text(0).unwrap_or_default();
+    let register = register(inv, 1)?.unwrap_or_else(|| editor.macros.last());
+    let actions = editor.macro_actions(register).ok_or_else(|| format!("No macro in register {}", register))?;
+    let path = macros::saved_path().ok_or("No config directory to save macros in")?;
+    macros::save(&path, name, &actions)?;
+    editor.messages.info(format!("Saved @{} as {}", register, name));
+    Ok(())
+}
+
+fn macro_load(editor: &mut Editor, inv: &Invocation) -> Result<(), String> {
+    let name = inv.text(0).unwrap_or_default();
+    let register = register(inv, 1)?.unwrap_or_else(|| editor.macros.last());
+    let path = macros::saved_path().ok_or("No config directory to load macros from")?;
+    let text = macros::saved_text(&macros::load_saved(&path)?, name)
+        .ok_or_else(|| format!("No saved macro named {}", name))?;
+    let actions = macros::parse(&text).map_err(|e| format!("{}: {}", name, e))?;
+    editor.registers.set(register, Yank::new(vec![macros::to_text(&actions)], true));
+    editor.macros.set_last(register);
+    editor.messages.info(format!("Loaded {} into @{}", name, register));
+    Ok(())
+}
+
+fn list_macros(editor: &mut Editor, _: &Invocation) -> Result<(), String> {
+    let path = macros::saved_path().ok_or("No config directory to load macros from")?;
+    let saved = macros::load_saved(&path)?;
+    let text: String = saved.keys()
+        .filter_map(|name| Some((name, macros::saved_text(&saved, name)?)))
+        .map(|(name, text)| format!("{}\n{}\n", name, text.lines().map(|l| format!("    {}\n", l)).collect::<String>()))
+        .collect();
+    editor.show_scratch("[macros]", &text);
+    Ok(())
+}
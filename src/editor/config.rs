@@ -1,9 +1,126 @@
+use std::{fs, io, path::{Path, PathBuf}, time::Duration};
+use directories_next::ProjectDirs;
+use crate::editor::{clipboard::Provider, status::Segment};
+
+const OPTIONS_FILE: &str = "config.toml";
+
+/// Where the editor's config files live.
+pub fn config_dir() -> Option<PathBuf> {
+    ProjectDirs::from("io", "pecu", "ded").map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// Where the editor keeps what it writes for itself, such as crash logs.
+pub fn data_dir() -> Option<PathBuf> {
+    ProjectDirs::from("io", "pecu", "ded").map(|dirs| dirs.data_local_dir().to_path_buf())
+}
+
 #[derive(Debug)]
 pub struct Config {
+    pub undo_file: bool,
+    pub tab_width: usize,
+    pub clipboard: Provider,
+    /// Copy to and paste from the system clipboard with every register, not just `+`.
+    pub clipboard_sync: bool,
+    pub status: Vec<Segment>,
+    /// How long a message stays in the bottom line.
+    pub message_timeout: Duration,
+    /// How long to wait for the next key of a sequence.
+    pub key_timeout: Duration,
+    /// Vi-style modes. Without them every key types or runs its binding straight away.
+    pub modal: bool,
+    /// Line numbers in a gutter left of the text.
+    pub line_numbers: bool,
+}
 
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            undo_file: false,
+            tab_width: 4,
+            clipboard: Provider::detect(),
+            clipboard_sync: false,
+            status: Segment::defaults(),
+            message_timeout: Duration::from_secs(5),
+            key_timeout: Duration::from_millis(1000),
+            modal: false,
+            line_numbers: false,
+        }
+    }
 }
 
 impl Config {
-    pub fn new() -> Self { Self {  } }
-}
+    pub fn new() -> Self { Self::default() }
+
+    /// Where options are read from at startup, `config.toml` in the config directory.
+    pub fn options_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join(OPTIONS_FILE))
+    }
+
+    /// Sets the options in the TOML file at `path`, as `name = value` pairs, returning
+    /// what couldn't be set. A missing file is not an error.
+    pub fn load_file(&mut self, path: &Path) -> Vec<String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+            Err(e) => return vec![format!("{}: {}", path.display(), e)],
+        };
+        let table: toml::Table = match toml::from_str(&text) {
+            Ok(table) => table,
+            Err(e) => return vec![format!("{}: {}", path.display(), e.message())],
+        };
+        table.iter()
+            .filter_map(|(name, value)| {
+                let value = match value {
+                    toml::Value::String(s) => s.clone(),
+                    value => value.to_string(),
+                };
+                self.set(name, Some(&value)).err()
+            })
+            .map(|e| format!("{}: {}", path.display(), e))
+            .collect()
+    }
 
+    /// Sets an option by name, as in `set tab_width=8`. A bool option given no value is
+    /// switched on, and off when prefixed with `no`.
+    pub fn set(&mut self, name: &str, value: Option<&str>) -> Result<(), String> {
+        let (name, value) = match (name.strip_prefix("no"), value) {
+            (Some(bool_name), None) if Self::is_bool(bool_name) => (bool_name, "false"),
+            (_, None) if Self::is_bool(name) => (name, "true"),
+            (_, None) if Self::is_option(name) => return Err(format!("{} needs a value", name)),
+            (_, None) => return Err(format!("unknown option: {}", name)),
+            (_, Some(value)) => (name, value),
+        };
+        let invalid = |e: &dyn std::fmt::Display| format!("invalid value for {}: {}", name, e);
+        match name {
+            "undo_file" | "undofile" => self.undo_file = value.parse().map_err(|e| invalid(&e))?,
+            "tab_width" | "tabstop" | "ts" => self.tab_width = value.parse().map_err(|e| invalid(&e))?,
+            "clipboard" => self.clipboard = value.parse().map_err(|e| invalid(&e))?,
+            "clipboard_sync" => self.clipboard_sync = value.parse().map_err(|e| invalid(&e))?,
+            "line_numbers" | "number" | "nu" => self.line_numbers = value.parse().map_err(|e| invalid(&e))?,
+            "modal" => self.modal = value.parse().map_err(|e| invalid(&e))?,
+            "modeless" => self.modal = !value.parse::<bool>().map_err(|e| invalid(&e))?,
+            "message_timeout" => {
+                self.message_timeout = Duration::from_millis(value.parse().map_err(|e| invalid(&e))?)
+            },
+            "key_timeout" | "timeoutlen" => {
+                self.key_timeout = Duration::from_millis(value.parse().map_err(|e| invalid(&e))?)
+            },
+            "status" => self.status = value.split(',')
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|e| invalid(&e))?,
+            _ => return Err(format!("unknown option: {}", name)),
+        }
+        Ok(())
+    }
+
+    fn is_option(name: &str) -> bool {
+        Self::is_bool(name) || matches!(name,
+            "tab_width" | "tabstop" | "ts" | "clipboard" | "message_timeout" | "key_timeout" | "timeoutlen" | "status")
+    }
+
+    fn is_bool(name: &str) -> bool {
+        matches!(name,
+            "undo_file" | "undofile" | "clipboard_sync" | "modal" | "modeless" | "line_numbers" | "number" | "nu")
+    }
+}
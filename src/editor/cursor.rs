@@ -0,0 +1,105 @@
+use crate::{editor::Coords, file::OpenFile};
+
+/// A position in the document: a line and a grapheme column on it. Vertical motion
+/// remembers the display column it started from, so moving through short lines and back
+/// returns to the same place.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub line: usize,
+    pub col: usize,
+    want: Option<usize>,
+}
+
+impl Cursor {
+
+    pub fn new(line: usize, col: usize) -> Self {
+        Self { line, col, want: None }
+    }
+
+    pub fn coords(&self) -> Coords {
+        Coords { x: self.col, y: self.line }
+    }
+
+    pub fn set(&mut self, at: Coords) {
+        self.line = at.y;
+        self.col = at.x;
+        self.want = None;
+    }
+
+    pub fn display_col(&self, file: &OpenFile, tab_width: usize) -> usize {
+        file.get(self.line).map_or(0, |row| row.width_to(self.col, tab_width))
+    }
+
+    pub fn left(&mut self, file: &OpenFile, n: usize) {
+        for _ in 0..n {
+            if self.col > 0 {
+                self.col -= 1;
+            } else if self.line > 0 {
+                self.line -= 1;
+                self.col = line_len(file, self.line);
+            }
+        }
+        self.want = None;
+    }
+
+    pub fn right(&mut self, file: &OpenFile, n: usize) {
+        let mut len = line_len(file, self.line);
+        for _ in 0..n {
+            if self.col < len {
+                self.col += 1;
+            } else if self.line + 1 < file.len() {
+                self.line += 1;
+                self.col = 0;
+                len = line_len(file, self.line);
+            }
+        }
+        self.want = None;
+    }
+
+    /// Like `left`, but stops at the start of the line.
+    pub fn left_in_line(&mut self, n: usize) {
+        self.col = self.col.saturating_sub(n);
+        self.want = None;
+    }
+
+    /// Like `right`, but stops at the last char of the line, or just after it if
+    /// `past_end` is set.
+    pub fn right_in_line(&mut self, file: &OpenFile, n: usize, past_end: bool) {
+        let len = line_len(file, self.line);
+        let last = if past_end { len } else { len.saturating_sub(1) };
+        self.col = self.col.saturating_add(n).min(last.max(self.col));
+        self.want = None;
+    }
+
+    pub fn up(&mut self, file: &OpenFile, n: usize, tab_width: usize) {
+        self.vertical(file, self.line.saturating_sub(n), tab_width)
+    }
+
+    pub fn down(&mut self, file: &OpenFile, n: usize, tab_width: usize) {
+        let last = file.len().saturating_sub(1);
+        self.vertical(file, self.line.saturating_add(n).min(last), tab_width)
+    }
+
+    fn vertical(&mut self, file: &OpenFile, line: usize, tab_width: usize) {
+        let want = self.want.unwrap_or_else(|| self.display_col(file, tab_width));
+        self.line = line;
+        self.col = file.get(line).map_or(0, |row| row.col_at(want, tab_width));
+        self.want = Some(want);
+    }
+
+    /// Pulls the cursor back inside the document after it shrank underneath it.
+    pub fn clamp(&mut self, file: &OpenFile) {
+        self.line = self.line.min(file.len().saturating_sub(1));
+        self.col = self.col.min(line_len(file, self.line));
+    }
+
+    /// Keeps the cursor on a char of its line, as normal mode wants it, rather than just
+    /// after the last one.
+    pub fn clamp_to_line(&mut self, file: &OpenFile) {
+        self.col = self.col.min(line_len(file, self.line).saturating_sub(1));
+    }
+}
+
+fn line_len(file: &OpenFile, line: usize) -> usize {
+    file.get(line).map_or(0, |row| row.len())
+}
@@ -0,0 +1,537 @@
+use std::{collections::HashMap, fmt, fs, io, path::{Path, PathBuf}, str::FromStr};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::{editor::{action::Action, config}, event::key};
+
+const KEYS_FILE: &str = "keys.toml";
+
+/// Keys and the actions they're bound to. Several actions for one binding are separated
+/// by `;`.
+type Table = &'static [(&'static str, &'static str)];
+
+/// The built-in bindings, active in every mode and filetype unless a layer overrides them.
+const DEFAULTS: Table = &[
+    ("up", "move up"),
+    ("down", "move down"),
+    ("left", "move left"),
+    ("right", "move right"),
+    ("end", "move to end line"),
+    ("home", "move to start line"),
+    ("pageup", "scroll up 5"),
+    ("pagedown", "scroll down 5"),
+    ("delete", "delete right"),
+    ("backspace", "delete left"),
+    ("enter", "newline right"),
+    ("tab", "input \\t"),
+    ("esc", "clear_selections"),
+
+    ("ctrl+shift+tab", "tab next"),
+    ("ctrl+shift+z", "redo"),
+    ("ctrl+shift+l", "add_cursor line"),
+    ("ctrl+shift+enter", "newline up"),
+
+    ("ctrl+j", "move down"),
+    ("ctrl+k", "move up"),
+    ("ctrl+h", "move left"),
+    ("ctrl+l", "move right"),
+    ("ctrl+q", "quit"),
+    ("ctrl+z", "undo"),
+    ("ctrl+y", "redo"),
+    ("ctrl+d", "add_cursor word"),
+    ("ctrl+c", "copy selection"),
+    ("ctrl+x", "delete over selection"),
+    ("ctrl+v", "paste"),
+    ("ctrl+w", "delete to start word"),
+    ("ctrl+p", "prompt command"),
+    ("ctrl+o", "prompt path"),
+    ("ctrl+f", "prompt search"),
+    ("ctrl+s", ":write"),
+    ("ctrl+up", "scroll up 5"),
+    ("ctrl+down", "scroll down 5"),
+    ("ctrl+left", "scroll left 5"),
+    ("ctrl+right", "scroll right 5"),
+    ("ctrl+tab", "tab prev"),
+    ("ctrl+home", "move to start document"),
+    ("ctrl+end", "move to end document"),
+    ("ctrl+enter", "newline down"),
+
+    ("alt+up", "move_text line up"),
+    ("alt+down", "move_text line down"),
+    ("alt+left", "move_text word left"),
+    ("alt+right", "move_text word right"),
+    ("alt+w", "select over word"),
+    ("alt+l", "select over line"),
+    ("alt+b", "move to start word"),
+    ("alt+f", "move to end word"),
+    ("alt+{", "move to start paragraph"),
+    ("alt+}", "move to end paragraph"),
+    ("alt+p", "select over bracketed"),
+    ("alt+y", "paste_cycle"),
+    ("alt+k", "delete to end line"),
+    ("alt+d", "delete to end word"),
+    ("alt+backspace", "delete to start word"),
+
+    ("shift+pageup", "move to start document"),
+    ("shift+pagedown", "move to end document"),
+    ("shift+home", "select to start line"),
+    ("shift+end", "select to end line"),
+    ("shift+up", "select up"),
+    ("shift+down", "select down"),
+    ("shift+left", "select left"),
+    ("shift+right", "select right"),
+    ("shift+enter", "newline right"),
+
+    ("f3", "record"),
+    ("f4", "replay"),
+];
+
+const INSERT: Table = &[
+    ("esc", "clear_selections; mode normal"),
+];
+
+/// Moving around, in normal and visual mode and as the target of an operator.
+const MOTIONS: Table = &[
+    ("h", "move left"),
+    ("j", "move down"),
+    ("k", "move up"),
+    ("l", "move right"),
+    ("space", "move right"),
+    ("backspace", "move left"),
+    ("w", "move to next word"),
+    ("b", "move to start word"),
+    ("e", "move to end word"),
+    ("^", "move to start line"),
+    ("$", "move to end line"),
+    ("g g", "move to start document"),
+    ("G", "move to end document"),
+    ("{", "move to start paragraph"),
+    ("}", "move to end paragraph"),
+    ("0", "count 0"),
+    ("1", "count 1"),
+    ("2", "count 2"),
+    ("3", "count 3"),
+    ("4", "count 4"),
+    ("5", "count 5"),
+    ("6", "count 6"),
+    ("7", "count 7"),
+    ("8", "count 8"),
+    ("9", "count 9"),
+];
+
+const NORMAL: Table = &[
+    ("i", "mode insert"),
+    ("a", "append"),
+    ("I", "move to start line; mode insert"),
+    ("A", "mode insert; move to end line"),
+    ("o", "newline down; mode insert"),
+    ("O", "newline up; mode insert"),
+    ("v", "mode visual"),
+    ("V", "mode visual_line"),
+    ("ctrl+v", "mode visual_block"),
+    ("d", "operator delete"),
+    ("c", "operator change"),
+    ("y", "operator yank"),
+    ("D", "delete to end line"),
+    ("C", "delete to end line; mode insert"),
+    ("Y", "operator yank; operator yank"),
+    ("x", "delete right"),
+    ("X", "delete left"),
+    ("s", "delete right; mode insert"),
+    ("p", "paste"),
+    ("u", "undo"),
+    ("ctrl+r", "redo"),
+    ("q", "record"),
+    ("@ @", "replay"),
+    (":", "prompt command"),
+    ("/", "prompt search"),
+    ("esc", "clear_selections"),
+];
+
+const VISUAL: Table = &[
+    ("d", "operator delete"),
+    ("x", "operator delete"),
+    ("c", "operator change"),
+    ("s", "operator change"),
+    ("y", "operator yank"),
+    ("v", "mode visual"),
+    ("V", "mode visual_line"),
+    ("ctrl+v", "mode visual_block"),
+    (":", "prompt command"),
+    ("@ @", "replay"),
+    ("esc", "mode normal"),
+];
+
+const OPERATOR: Table = &[
+    ("d", "operator delete"),
+    ("c", "operator change"),
+    ("y", "operator yank"),
+    ("esc", "mode normal"),
+];
+
+/// Text objects, selected in visual mode or operated on after an operator, as in `ci"`.
+const TEXT_OBJECTS: Table = &[
+    ("i w", "select over inner word"),
+    ("a w", "select over around word"),
+    ("i p", "select over inner paragraph"),
+    ("a p", "select over around paragraph"),
+    ("i \"", "select over inner double_quotes"),
+    ("a \"", "select over around double_quotes"),
+    ("i '", "select over inner single_quotes"),
+    ("a '", "select over around single_quotes"),
+    ("i `", "select over inner backticks"),
+    ("a `", "select over around backticks"),
+    ("i (", "select over inner parens"),
+    ("a (", "select over around parens"),
+    ("i )", "select over inner parens"),
+    ("a )", "select over around parens"),
+    ("i b", "select over inner parens"),
+    ("a b", "select over around parens"),
+    ("i [", "select over inner char ["),
+    ("a [", "select over around char ["),
+    ("i ]", "select over inner char ["),
+    ("a ]", "select over around char ["),
+    ("i {", "select over inner char {"),
+    ("a {", "select over around char {"),
+    ("i }", "select over inner char {"),
+    ("a }", "select over around char {"),
+    ("i B", "select over inner char {"),
+    ("a B", "select over around char {"),
+];
+
+/// The built-in layers: the mode they belong to, if any, and their tables.
+const LAYERS: &[(Option<&str>, Table)] = &[
+    (None, DEFAULTS),
+    (Some("insert"), INSERT),
+    (Some("normal"), MOTIONS),
+    (Some("normal"), NORMAL),
+    (Some("visual"), MOTIONS),
+    (Some("visual"), VISUAL),
+    (Some("visual"), TEXT_OBJECTS),
+    (Some("visual_line"), MOTIONS),
+    (Some("visual_line"), VISUAL),
+    (Some("visual_block"), MOTIONS),
+    (Some("visual_block"), VISUAL),
+    (Some("operator"), MOTIONS),
+    (Some("operator"), OPERATOR),
+    (Some("operator"), TEXT_OBJECTS),
+];
+
+/// A key with its modifiers, e.g. `ctrl+shift+z` or `alt+backspace`. Shifted chars are
+/// stored as the char they type, so `shift+a` and `A` are the same chord.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => Self {
+                code: KeyCode::Char(c.to_uppercase().next().unwrap_or(c)),
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            code => Self { code, modifiers },
+        }
+    }
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(key: KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        // A trailing `+` is the plus key itself, as in `ctrl++`.
+        let (mods, key) = match s.strip_suffix("++") {
+            Some(mods) => (mods, "+"),
+            None => s.rsplit_once('+').unwrap_or(("", s)),
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        for m in mods.split('+').filter(|m| !m.is_empty()) {
+            modifiers |= match m.to_lowercase().as_str() {
+                "ctrl" | "control" | "c" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier: {}", m)),
+            };
+        }
+        let code = match key.to_lowercase().as_str() {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "enter" | "return" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "esc" | "escape" => KeyCode::Esc,
+            "space" => KeyCode::Char(' '),
+            "plus" => KeyCode::Char('+'),
+            f if f.len() > 1 && f.starts_with('f') && f[1..].parse::<u8>().is_ok() => {
+                KeyCode::F(f[1..].parse().unwrap_or_default())
+            },
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(format!("unknown key: {}", key)),
+                }
+            },
+        };
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl+"),
+            (KeyModifiers::ALT, "alt+"),
+            (KeyModifiers::SHIFT, "shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}", name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::BackTab => write!(f, "backtab"),
+            code => write!(f, "{}", format!("{:?}", code).to_lowercase()),
+        }
+    }
+}
+
+/// A sequence of chords separated by blanks, like `g g` or `ctrl+x ctrl+s`.
+pub fn parse_keys(s: &str) -> Result<Vec<KeyChord>, String> {
+    let keys = s.split_whitespace().map(str::parse).collect::<Result<Vec<KeyChord>, _>>()?;
+    if keys.is_empty() {
+        return Err("no keys".into());
+    }
+    Ok(keys)
+}
+
+pub fn keys_to_string(keys: &[KeyChord]) -> String {
+    keys.iter().map(KeyChord::to_string).collect::<Vec<_>>().join(" ")
+}
+
+/// Which bindings a layer applies to. `None` matches any mode or filetype.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+struct Layer {
+    filetype: Option<String>,
+    mode: Option<String>,
+}
+
+/// A node of a layer's trie. The keys leading to it may run actions, start longer
+/// sequences, or both.
+#[derive(Debug, Clone, Default)]
+struct Node {
+    actions: Option<key::Action>,
+    next: HashMap<KeyChord, Node>,
+}
+
+/// What the keys pressed so far resolve to.
+#[derive(Debug, Clone)]
+pub enum Lookup {
+    /// A complete binding.
+    Found(key::Action),
+    /// The start of longer bindings. The actions the keys are bound to themselves, if
+    /// any, run when no other key follows in time.
+    Prefix(Option<key::Action>),
+    Unbound,
+}
+
+/// Key sequences mapped to actions, in layers per mode and per filetype. Lookups try the
+/// most specific layer first, down to the built-in defaults.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    layers: HashMap<Layer, Node>,
+}
+
+impl Keymap {
+
+    /// The built-in keymap, with no user bindings.
+    pub fn new() -> Self {
+        let mut keymap = Self { layers: HashMap::new() };
+        for (mode, table) in LAYERS {
+            for (keys, actions) in table.iter() {
+                let keys = parse_keys(keys).expect("Invalid default key");
+                let actions = actions.split(';')
+                    .map(|action| action.parse::<Action>().expect("Invalid default action"))
+                    .collect::<Vec<_>>();
+                keymap.bind(None, *mode, &keys, actions.into());
+            }
+        }
+        // `q` and a register records into it, or appends to it when uppercase. `@` and a
+        // register replays it.
+        let key = |c| KeyChord::new(KeyCode::Char(c), KeyModifiers::NONE);
+        for register in ('a'..='z').chain('A'..='Z').chain('0'..='9') {
+            keymap.bind(None, Some("normal"), &[key('q'), key(register)], Action::Record(Some(register)).into());
+            if register.is_ascii_uppercase() {
+                continue;
+            }
+            for mode in ["normal", "visual", "visual_line", "visual_block"].iter() {
+                keymap.bind(None, Some(mode), &[key('@'), key(register)], Action::Replay(Some(register)).into());
+            }
+        }
+        keymap
+    }
+
+    /// Where the user's keymap is read from, `keys.toml` in the config directory.
+    pub fn config_path() -> Option<PathBuf> {
+        config::config_dir().map(|dir| dir.join(KEYS_FILE))
+    }
+
+    /// Binds `keys` in the layer for `filetype` and `mode`. Binding `none` hides the
+    /// bindings of less specific layers.
+    pub fn bind(&mut self, filetype: Option<&str>, mode: Option<&str>, keys: &[KeyChord], actions: key::Action) {
+        let layer = Layer { filetype: filetype.map(String::from), mode: mode.map(String::from) };
+        let node = keys.iter().fold(self.layers.entry(layer).or_default(), |node, chord| {
+            node.next.entry(*chord).or_default()
+        });
+        node.actions = Some(actions);
+    }
+
+    /// What `keys` are bound to in `mode`, for a file of `filetype`.
+    pub fn lookup(&self, keys: &[KeyChord], mode: &str, filetype: &str) -> Lookup {
+        let layers = [
+            (Some(filetype), Some(mode)),
+            (Some(filetype), None),
+            (None, Some(mode)),
+            (None, None),
+        ];
+        let nodes: Vec<&Node> = layers.iter()
+            .filter_map(|(filetype, mode)| {
+                let layer = Layer { filetype: filetype.map(String::from), mode: mode.map(String::from) };
+                keys.iter().try_fold(self.layers.get(&layer)?, |node, chord| node.next.get(chord))
+            })
+            .collect();
+        let actions = nodes.iter()
+            .find_map(|node| node.actions.as_ref())
+            .filter(|actions| !actions.is_unbound())
+            .cloned();
+        match (actions, nodes.iter().any(|node| !node.next.is_empty())) {
+            (actions, true) => Lookup::Prefix(actions),
+            (Some(actions), false) => Lookup::Found(actions),
+            (None, false) => Lookup::Unbound,
+        }
+    }
+
+    /// What an unbound chord does in `mode`: chars are typed as they are when typing.
+    pub fn fallback(chord: KeyChord, mode: &str) -> Action {
+        match chord.code {
+            KeyCode::Char(c) if chord.modifiers.is_empty() && matches!(mode, "insert" | "prompt") => {
+                Action::Input(c.to_string())
+            },
+            _ => Action::from(chord),
+        }
+    }
+
+    /// Reads bindings from the TOML file at `path`, returning what couldn't be loaded. A
+    /// missing file is not an error.
+    pub fn load_file(&mut self, path: &Path) -> Vec<String> {
+        match fs::read_to_string(path) {
+            Ok(text) => self.load(&text).into_iter()
+                .map(|e| format!("{}: {}", path.display(), e))
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => vec![format!("{}: {}", path.display(), e)],
+        }
+    }
+
+    /// Adds the bindings in `text`, skipping and returning the ones that don't parse.
+    /// Tables name the layer and map key sequences to actions, `:commands`, or arrays of
+    /// them run in order:
+    ///
+    /// ```toml
+    /// [global]
+    /// "ctrl+x ctrl+s" = ":write"
+    /// [insert]
+    /// "alt+j" = "move down 5"
+    /// [normal]
+    /// "g g" = "move to start document"
+    /// [filetype.rust]
+    /// "ctrl+b" = [":write", ":make"]
+    /// [filetype.rust.normal]
+    /// "ctrl+b" = "none"
+    /// ```
+    pub fn load(&mut self, text: &str) -> Vec<String> {
+        let table: toml::Table = match toml::from_str(text) {
+            Ok(table) => table,
+            Err(e) => return vec![e.message().to_string()],
+        };
+        let mut errors = Vec::new();
+        for (name, value) in &table {
+            match (name.as_str(), value) {
+                ("filetype", toml::Value::Table(filetypes)) => for (filetype, value) in filetypes {
+                    match value {
+                        toml::Value::Table(table) => self.load_filetype(filetype, table, &mut errors),
+                        _ => errors.push(format!("filetype.{} should be a table", filetype)),
+                    }
+                },
+                (mode, toml::Value::Table(bindings)) => self.load_mode(None, mode, bindings, &mut errors),
+                _ => errors.push(format!("{} should be a table", name)),
+            }
+        }
+        errors
+    }
+
+    /// Loads a filetype's table: strings are bindings for every mode, tables hold the
+    /// bindings of the mode they're named after.
+    fn load_filetype(&mut self, filetype: &str, table: &toml::Table, errors: &mut Vec<String>) {
+        for (name, value) in table {
+            match value {
+                toml::Value::Table(bindings) => self.load_mode(Some(filetype), name, bindings, errors),
+                action => if let Err(e) = self.load_binding(Some(filetype), None, name, action) {
+                    errors.push(e);
+                },
+            }
+        }
+    }
+
+    /// Loads the bindings of `mode`, or of every mode if it is `global`.
+    fn load_mode(&mut self, filetype: Option<&str>, mode: &str, bindings: &toml::Table, errors: &mut Vec<String>) {
+        let mode = Some(mode).filter(|m| *m != "global");
+        for (chord, action) in bindings {
+            if let Err(e) = self.load_binding(filetype, mode, chord, action) {
+                errors.push(e);
+            }
+        }
+    }
+
+    fn load_binding(&mut self, filetype: Option<&str>, mode: Option<&str>, keys: &str, value: &toml::Value) -> Result<(), String> {
+        let error = |e: String| format!("{}: {}", keys, e);
+        let parse = |value: &toml::Value| match value {
+            toml::Value::String(action) => action.parse::<Action>(),
+            _ => Err("expected an action".to_string()),
+        };
+        let actions = match value {
+            toml::Value::Array(values) => values.iter().map(parse).collect::<Result<Vec<_>, _>>(),
+            value => parse(value).map(|action| vec![action]),
+        }.map_err(error)?;
+        let chords = parse_keys(keys).map_err(error)?;
+        self.bind(filetype, mode, &chords, actions.into());
+        Ok(())
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,148 @@
+use std::{fs, io, path::{Path, PathBuf}};
+use crate::editor::{action::Action, config};
+
+pub const DEFAULT_REGISTER: char = 'q';
+const MACROS_FILE: &str = "macros.toml";
+/// How deep macros may replay other macros, so one replaying itself stops.
+const MAX_DEPTH: usize = 100;
+
+/// Keyboard macros. Recorded actions are kept in a register as text, one action per
+/// line, so a macro can be pasted, edited and yanked back like any other text.
+#[derive(Debug, Default)]
+pub struct Macros {
+    recording: Option<(char, Vec<Action>)>,
+    last: Option<char>,
+    depth: usize,
+}
+
+impl Macros {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The register being recorded into, if any.
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(name, _)| *name)
+    }
+
+    pub fn start(&mut self, register: char) {
+        self.recording = Some((register, Vec::new()));
+    }
+
+    /// Stops recording, returning the register and what was recorded into it.
+    pub fn stop(&mut self) -> Option<(char, Vec<Action>)> {
+        let (name, actions) = self.recording.take()?;
+        self.last = Some(name.to_ascii_lowercase());
+        Some((name, actions))
+    }
+
+    /// Adds an action run from the keyboard to the recording. Actions run by a macro are
+    /// not recorded, and neither are keys that do nothing.
+    pub fn record(&mut self, action: &Action) {
+        let actions = match &mut self.recording {
+            Some((_, actions)) if self.depth == 0 => actions,
+            _ => return,
+        };
+        match (actions.last_mut(), action) {
+            (_, Action::Record(_)) | (_, Action::Unregistered(_)) => {},
+            // Typed text is kept together, except tabs and line breaks, which a prompt
+            // treats differently.
+            (Some(Action::Input(last)), Action::Input(text)) if is_plain(last) && is_plain(text) => {
+                last.push_str(text)
+            },
+            _ => actions.push(action.clone()),
+        }
+    }
+
+    /// The register replayed when none is given: the last one recorded or replayed.
+    pub fn last(&self) -> char {
+        self.last.unwrap_or(DEFAULT_REGISTER)
+    }
+
+    pub fn set_last(&mut self, register: char) {
+        self.last = Some(register.to_ascii_lowercase());
+    }
+
+    /// Marks the start of a replay, failing when macros nest too deep.
+    pub fn enter(&mut self) -> Result<(), String> {
+        if self.depth >= MAX_DEPTH {
+            return Err("Macros nested too deep".into());
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+}
+
+fn is_plain(text: &str) -> bool {
+    !text.chars().any(char::is_control)
+}
+
+pub fn to_text(actions: &[Action]) -> String {
+    actions.iter().map(|action| format!("{}\n", action)).collect()
+}
+
+/// Parses a macro's text. Blank lines and lines starting with `#` are skipped.
+pub fn parse(text: &str) -> Result<Vec<Action>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| line.parse().map_err(|e| format!("line {}: {}", i + 1, e)))
+        .collect()
+}
+
+/// The register a `[macro x]` buffer opened to edit a macro writes back to.
+pub fn buffer_register(path: &Path) -> Option<char> {
+    let name = path.to_str()?.strip_prefix("[macro ")?.strip_suffix(']')?;
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+pub fn buffer_name(register: char) -> String {
+    format!("[macro {}]", register)
+}
+
+/// Where named macros are saved, `macros.toml` in the config directory.
+pub fn saved_path() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join(MACROS_FILE))
+}
+
+/// Every saved macro, by name. Each is stored as an array of actions.
+pub fn load_saved(path: &Path) -> Result<toml::Table, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e.message())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// The text of the macro saved as `name`.
+pub fn saved_text(table: &toml::Table, name: &str) -> Option<String> {
+    match table.get(name)? {
+        toml::Value::Array(actions) => Some(actions.iter()
+            .filter_map(|action| action.as_str())
+            .map(|action| format!("{}\n", action))
+            .collect()),
+        toml::Value::String(text) => Some(text.clone()),
+        _ => None,
+    }
+}
+
+/// Saves `actions` as `name`, keeping the other saved macros.
+pub fn save(path: &Path, name: &str, actions: &[Action]) -> Result<(), String> {
+    let mut table = load_saved(path)?;
+    let actions = actions.iter().map(|action| toml::Value::String(action.to_string())).collect();
+    table.insert(name.to_string(), toml::Value::Array(actions));
+    let text = toml::to_string(&table).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub level: Level,
+    pub text: String,
+    pub time: SystemTime,
+}
+
+/// Everything posted for the user this session. The latest message is shown in the
+/// bottom line until it times out, is dismissed, or another one replaces it.
+#[derive(Debug, Default)]
+pub struct Messages {
+    log: Vec<Message>,
+    shown: Option<Instant>,
+}
+
+impl Messages {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn post<S: Into<String>>(&mut self, level: Level, text: S) {
+        self.log.push(Message { level, text: text.into(), time: SystemTime::now() });
+        self.shown = Some(Instant::now());
+    }
+
+    pub fn info<S: Into<String>>(&mut self, text: S) {
+        self.post(Level::Info, text)
+    }
+
+    pub fn warn<S: Into<String>>(&mut self, text: S) {
+        self.post(Level::Warning, text)
+    }
+
+    pub fn error<S: Into<String>>(&mut self, text: S) {
+        self.post(Level::Error, text)
+    }
+
+    /// The message to show now, if it was posted less than `timeout` ago.
+    pub fn current(&self, timeout: Duration) -> Option<&Message> {
+        self.time_left(timeout)?;
+        self.log.last()
+    }
+
+    /// How long until the shown message times out.
+    pub fn time_left(&self, timeout: Duration) -> Option<Duration> {
+        let left = timeout.checked_sub(self.shown?.elapsed())?;
+        if left > Duration::from_millis(0) { Some(left) } else { None }
+    }
+
+    pub fn dismiss(&mut self) {
+        self.shown = None;
+    }
+
+    pub fn history(&self) -> &[Message] {
+        &self.log
+    }
+
+    /// The history as text, oldest first, with the UTC time each message was posted.
+    pub fn to_text(&self) -> String {
+        self.log.iter()
+            .map(|msg| {
+                let secs = msg.time.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+                let time = format!("{:02}:{:02}:{:02}", secs / 3600 % 24, secs / 60 % 60, secs % 60);
+                let text = msg.text.replace('\n', &format!("\n{:17}", ""));
+                format!("{} {:<7} {}\n", time, msg.level.name(), text)
+            })
+            .collect()
+    }
+}
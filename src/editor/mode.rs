@@ -0,0 +1,107 @@
+use std::{fmt, str::FromStr};
+use crate::term::CursorShape;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visual {
+    Char,
+    Line,
+    Block,
+}
+
+/// An edit applied to the text a motion moves over, or to the visual selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+/// What keys do. Insert mode types text; the others move, select and run operators.
+/// Without modal editing the editor stays in insert mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    Insert,
+    Visual(Visual),
+    /// An operator waiting for a motion or text object.
+    Pending(Operator),
+}
+
+impl Mode {
+
+    /// The name of the keymap layer for this mode.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Insert => "insert",
+            Mode::Visual(Visual::Char) => "visual",
+            Mode::Visual(Visual::Line) => "visual_line",
+            Mode::Visual(Visual::Block) => "visual_block",
+            Mode::Pending(_) => "operator",
+        }
+    }
+
+    /// How the mode is shown in the status line.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Visual(Visual::Char) => "VISUAL",
+            Mode::Visual(Visual::Line) => "V-LINE",
+            Mode::Visual(Visual::Block) => "V-BLOCK",
+            Mode::Pending(_) => "O-PENDING",
+        }
+    }
+
+    pub fn cursor_shape(&self) -> CursorShape {
+        match self {
+            Mode::Insert => CursorShape::Bar,
+            Mode::Pending(_) => CursorShape::Underline,
+            Mode::Normal | Mode::Visual(_) => CursorShape::Block,
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "normal" => Mode::Normal,
+            "insert" => Mode::Insert,
+            "visual" => Mode::Visual(Visual::Char),
+            "visual_line" => Mode::Visual(Visual::Line),
+            "visual_block" => Mode::Visual(Visual::Block),
+            other => return Err(format!("unknown mode: {}", other)),
+        })
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Operator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "delete" => Operator::Delete,
+            "change" => Operator::Change,
+            "yank" => Operator::Yank,
+            other => return Err(format!("unknown operator: {}", other)),
+        })
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Delete => write!(f, "delete"),
+            Operator::Change => write!(f, "change"),
+            Operator::Yank => write!(f, "yank"),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{
+    buffer::Buffer,
+    editor::{action::{Location, Target}, textobj::{self, Class, class}},
+};
+
+/// Char index that moving from `head` to `location` lands on.
+pub fn to(buffer: &Buffer, location: &Location, head: usize) -> Option<usize> {
+    let head = head.min(buffer.len_chars());
+    let line = buffer.char_to_line(head);
+    match location {
+        Location::Index(col) => Some(buffer.grapheme_to_char(line, *col as usize)),
+        Location::Beginning(target) => beginning(buffer, target, head, line),
+        Location::End(target) => end(buffer, target, head, line),
+        Location::Next(target) => next(buffer, target, head),
+    }
+}
+
+fn next(buffer: &Buffer, target: &Target, head: usize) -> Option<usize> {
+    let len = buffer.len_chars();
+    match target {
+        Target::Word => {
+            let mut i = head;
+            if i < len && !is_space(buffer.char(i)) {
+                let kind = class(buffer.char(i));
+                while i < len && class(buffer.char(i)) == kind {
+                    i += 1;
+                }
+            }
+            while i < len && is_space(buffer.char(i)) {
+                i += 1;
+            }
+            Some(i)
+        },
+        target => {
+            let around = Target::Around(Box::new(target.clone()));
+            textobj::resolve(buffer, &around, head..head).map(|r| r.end)
+        },
+    }
+}
+
+fn beginning(buffer: &Buffer, target: &Target, head: usize, line: usize) -> Option<usize> {
+    match target {
+        Target::Document => Some(0),
+        Target::Line => {
+            let start = buffer.line_to_char(line);
+            let first = first_non_blank(buffer, line);
+            Some(if head == first { start } else { first })
+        },
+        Target::Word => {
+            let mut i = head;
+            while i > 0 && is_space(buffer.char(i - 1)) {
+                i -= 1;
+            }
+            if i > 0 {
+                let kind = class(buffer.char(i - 1));
+                while i > 0 && class(buffer.char(i - 1)) == kind {
+                    i -= 1;
+                }
+            }
+            Some(i)
+        },
+        Target::Paragraph => {
+            let mut l = line;
+            if head == buffer.line_to_char(line) || is_blank(buffer, line) {
+                if l == 0 {
+                    return Some(0);
+                }
+                l -= 1;
+                while l > 0 && is_blank(buffer, l) {
+                    l -= 1;
+                }
+            }
+            while l > 0 && !is_blank(buffer, l - 1) {
+                l -= 1;
+            }
+            Some(buffer.line_to_char(l))
+        },
+        Target::Char(c) => {
+            let start = buffer.line_to_char(line);
+            (start..head).rev().find(|&i| buffer.char(i) == *c)
+        },
+        Target::Inner(_) | Target::Around(_) | Target::Selection | Target::InputWord(_)
+            | Target::Region(..) => textobj::resolve(buffer, target, head..head).map(|r| r.start),
+        _ => {
+            let around = Target::Around(Box::new(target.clone()));
+            textobj::resolve(buffer, &around, head..head).map(|r| r.start)
+        },
+    }
+}
+
+fn end(buffer: &Buffer, target: &Target, head: usize, line: usize) -> Option<usize> {
+    let len = buffer.len_chars();
+    match target {
+        Target::Document => Some(len),
+        Target::Line => Some(line_end(buffer, line)),
+        Target::Word => {
+            let mut i = head;
+            while i < len && is_space(buffer.char(i)) {
+                i += 1;
+            }
+            if i < len {
+                let kind = class(buffer.char(i));
+                while i < len && class(buffer.char(i)) == kind {
+                    i += 1;
+                }
+            }
+            Some(i)
+        },
+        Target::Paragraph => {
+            let last = buffer.len_lines() - 1;
+            let mut l = line;
+            if head == line_end(buffer, line) || is_blank(buffer, line) {
+                if l == last {
+                    return Some(len);
+                }
+                l += 1;
+                while l < last && is_blank(buffer, l) {
+                    l += 1;
+                }
+            }
+            while l < last && !is_blank(buffer, l + 1) {
+                l += 1;
+            }
+            Some(line_end(buffer, l))
+        },
+        Target::Char(c) => {
+            let end = line_end(buffer, line);
+            (head + 1..end).find(|&i| buffer.char(i) == *c)
+        },
+        Target::Inner(_) | Target::Around(_) | Target::Selection | Target::InputWord(_)
+            | Target::Region(..) => textobj::resolve(buffer, target, head..head).map(|r| r.end),
+        _ => {
+            let around = Target::Around(Box::new(target.clone()));
+            textobj::resolve(buffer, &around, head..head).map(|r| r.end.saturating_sub(1))
+        },
+    }
+}
+
+fn is_space(c: char) -> bool {
+    matches!(class(c), Class::Blank | Class::Newline)
+}
+
+fn line_end(buffer: &Buffer, line: usize) -> usize {
+    buffer.line_to_char(line) + buffer.line_len(line)
+}
+
+fn first_non_blank(buffer: &Buffer, line: usize) -> usize {
+    let (start, end) = (buffer.line_to_char(line), line_end(buffer, line));
+    (start..end).find(|&i| class(buffer.char(i)) != Class::Blank).unwrap_or(end)
+}
+
+pub fn is_blank(buffer: &Buffer, line: usize) -> bool {
+    let start = buffer.line_to_char(line);
+    first_non_blank(buffer, line) == start + buffer.line_len(line)
+}
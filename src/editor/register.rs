@@ -0,0 +1,124 @@
+use std::collections::{HashMap, VecDeque};
+
+const KILL_RING_LEN: usize = 32;
+
+/// Text held by a register: one string per selection it was taken from, and whether it
+/// was taken as whole lines, which changes where it is pasted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Yank {
+    pub texts: Vec<String>,
+    pub linewise: bool,
+}
+
+impl Yank {
+
+    pub fn new(texts: Vec<String>, linewise: bool) -> Self {
+        Self { texts, linewise }
+    }
+
+    pub fn joined(&self) -> String {
+        self.texts.join("\n")
+    }
+}
+
+/// Vim-style registers shared by every file in the editor: `"` is written by every copy
+/// and delete, `0` by the last copy, and `a`-`z` only when selected first, with `A`-`Z`
+/// appending to them. Copies and deletes are also pushed onto an Emacs-style kill ring
+/// that a paste can cycle through.
+#[derive(Debug, Default)]
+pub struct Registers {
+    named: HashMap<char, Yank>,
+    kill_ring: VecDeque<Yank>,
+    ring_idx: usize,
+    selected: Option<char>,
+}
+
+impl Registers {
+
+    pub const UNNAMED: char = '"';
+    pub const LAST_YANK: char = '0';
+    pub const CLIPBOARD: char = '+';
+    pub const SELECTION: char = '*';
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the next copy, delete or paste use register `name`.
+    pub fn select(&mut self, name: char) {
+        if name.is_ascii_alphanumeric() || [Self::UNNAMED, Self::CLIPBOARD, Self::SELECTION].contains(&name) {
+            self.selected = Some(name);
+        }
+    }
+
+    pub fn selected(&self) -> Option<char> {
+        self.selected
+    }
+
+    /// Whether `name` stands for the system clipboard.
+    pub fn is_clipboard(name: char) -> bool {
+        name == Self::CLIPBOARD || name == Self::SELECTION
+    }
+
+    pub fn get(&self, name: char) -> Option<&Yank> {
+        self.named.get(&name.to_ascii_lowercase())
+    }
+
+    pub fn set(&mut self, name: char, yank: Yank) {
+        if name.is_ascii_uppercase() {
+            let lower = name.to_ascii_lowercase();
+            match self.named.get_mut(&lower) {
+                Some(existing) => {
+                    existing.linewise |= yank.linewise;
+                    existing.texts.extend(yank.texts);
+                },
+                None => { self.named.insert(lower, yank); },
+            }
+        } else {
+            self.named.insert(name, yank);
+        }
+    }
+
+    pub fn names(&self) -> Vec<char> {
+        let mut names: Vec<char> = self.named.keys().copied().collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn yank(&mut self, yank: Yank) {
+        if let Some(name) = self.selected.take() {
+            self.set(name, yank.clone());
+        }
+        self.named.insert(Self::LAST_YANK, yank.clone());
+        self.push_ring(yank);
+    }
+
+    pub fn kill(&mut self, yank: Yank) {
+        if let Some(name) = self.selected.take() {
+            self.set(name, yank.clone());
+        }
+        self.push_ring(yank);
+    }
+
+    fn push_ring(&mut self, yank: Yank) {
+        self.kill_ring.push_front(yank.clone());
+        self.kill_ring.truncate(KILL_RING_LEN);
+        self.named.insert(Self::UNNAMED, yank);
+    }
+
+    /// What a paste should insert, restarting the kill ring cycle.
+    pub fn paste(&mut self) -> Option<Yank> {
+        self.ring_idx = 0;
+        let name = self.selected.take().unwrap_or(Self::UNNAMED);
+        self.get(name).cloned()
+    }
+
+    /// The next older kill, to replace what the previous paste inserted.
+    pub fn cycle(&mut self) -> Option<Yank> {
+        if self.kill_ring.is_empty() {
+            return None;
+        }
+        self.ring_idx = (self.ring_idx + 1) % self.kill_ring.len();
+        self.kill_ring.get(self.ring_idx).cloned()
+    }
+}
@@ -1,54 +1,98 @@
-use std::cmp;
+use std::borrow::Cow;
+use ropey::RopeSlice;
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-#[derive(Debug)]
-pub struct Row {
-    row: String,
-    len: usize,
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'a> {
+    row: RopeSlice<'a>,
 }
 
-impl Row {
+impl<'a> Row<'a> {
 
-    pub fn render(&self, start: usize, end: usize) -> String {
-        let end = cmp::min(end as usize, self.row.len() as usize) as usize;
-        let start = cmp::min(start as usize, end) as usize;
-        self.row.get(start..end).unwrap_or_default().to_string();
+    /// Renders the display columns `start..start + width`, expanding tabs and padding
+    /// wide graphemes cut by either edge with spaces.
+    pub fn render(&self, start: usize, width: usize, tab_width: usize) -> String {
+        let end = start + width;
         let mut res = String::new();
-        for grapheme in self.row[..]
-            .graphemes(true)
-            .skip(start)
-            .take(end-start)
-        {
-            if grapheme == "\t" {
-                res.push_str(" ");
+        let mut x = 0;
+        for grapheme in self.text().graphemes(true) {
+            if x >= end {
+                break;
+            }
+            let w = grapheme_width(grapheme, x, tab_width);
+            if x + w <= start {
+                x += w;
+                continue;
+            }
+            let visible = (x + w).min(end) - x.max(start);
+            if grapheme == "\t" || visible < w {
+                res.push_str(&" ".repeat(visible));
             } else {
                 res.push_str(grapheme);
             }
+            x += w;
         }
         res
     }
 
+    /// Display column at which grapheme `col` starts.
+    pub fn width_to(&self, col: usize, tab_width: usize) -> usize {
+        self.text()
+            .graphemes(true)
+            .take(col)
+            .fold(0, |x, g| x + grapheme_width(g, x, tab_width))
+    }
+
+    /// Grapheme column covering display column `x`, or the row's length past its end.
+    pub fn col_at(&self, x: usize, tab_width: usize) -> usize {
+        let mut width = 0;
+        for (col, grapheme) in self.text().graphemes(true).enumerate() {
+            width += grapheme_width(grapheme, width, tab_width);
+            if width > x {
+                return col;
+            }
+        }
+        self.len()
+    }
+
+    pub fn text(&self) -> Cow<'a, str> {
+        self.row.into()
+    }
+
+    pub fn slice(&self) -> RopeSlice<'a> {
+        self.row
+    }
+
     pub fn len(&self) -> usize {
-        self.len
+        self.text().graphemes(true).count()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.len == 0_usize
+        self.row.len_chars() == 0
     }
+}
 
-    pub fn update_len(&mut self) {
-        self.len = self.row[..].graphemes(true).count();
+impl<'a> From<RopeSlice<'a>> for Row<'a> {
+
+    fn from(row: RopeSlice<'a>) -> Self {
+        Self { row }
     }
+
 }
 
-impl From<&str> for Row {
+impl<'a> From<&'a str> for Row<'a> {
 
-    fn from(string: &str) -> Self {
-        let mut row = Self {
-            row: String::from(string), len: 0
-        };
-        row.update_len();
-        row
+    fn from(string: &'a str) -> Self {
+        Self { row: string.into() }
     }
 
 }
+
+pub fn grapheme_width(grapheme: &str, x: usize, tab_width: usize) -> usize {
+    if grapheme == "\t" {
+        tab_width.max(1) - x % tab_width.max(1)
+    } else {
+        grapheme.width()
+    }
+}
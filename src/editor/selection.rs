@@ -0,0 +1,134 @@
+use std::ops::Range;
+use crate::{editor::cursor::Cursor, file::OpenFile};
+
+/// A selected range. The anchor stays put while motions move the head; an empty
+/// selection is a plain cursor.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub anchor: Cursor,
+    pub head: Cursor,
+}
+
+impl Selection {
+
+    pub fn point(head: Cursor) -> Self {
+        Self { anchor: head.clone(), head }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor.coords() == self.head.coords()
+    }
+
+    pub fn collapse(&mut self) {
+        self.anchor = self.head.clone();
+    }
+
+    /// Char range covered by the selection, in document order.
+    pub fn range(&self, file: &OpenFile) -> Range<usize> {
+        let (a, h) = (file.char_idx(&self.anchor.coords()), file.char_idx(&self.head.coords()));
+        a.min(h)..a.max(h)
+    }
+
+    pub fn set_range(&mut self, file: &OpenFile, chars: Range<usize>) {
+        self.anchor.set(file.coords(chars.start));
+        self.head.set(file.coords(chars.end));
+    }
+}
+
+/// Every selection in a window, kept sorted and non-overlapping. One of them is the
+/// primary selection that the view follows.
+#[derive(Debug, Clone)]
+pub struct Selections {
+    ranges: Vec<Selection>,
+    primary: usize,
+}
+
+impl Default for Selections {
+    fn default() -> Self {
+        Self { ranges: vec![Selection::default()], primary: 0 }
+    }
+}
+
+impl Selections {
+
+    pub fn primary(&self) -> &Selection {
+        &self.ranges[self.primary]
+    }
+
+    pub fn primary_mut(&mut self) -> &mut Selection {
+        &mut self.ranges[self.primary]
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Selection> {
+        self.ranges.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Selection> {
+        self.ranges.iter_mut()
+    }
+
+    /// Adds a selection and makes it the primary one.
+    pub fn push(&mut self, selection: Selection, file: &OpenFile) {
+        self.ranges.push(selection);
+        self.primary = self.ranges.len() - 1;
+        self.normalize(file);
+    }
+
+    /// Replaces every selection with a single one.
+    pub fn set(&mut self, selection: Selection) {
+        self.ranges = vec![selection];
+        self.primary = 0;
+    }
+
+    pub fn keep_primary(&mut self) {
+        let primary = self.ranges.swap_remove(self.primary);
+        self.set(primary);
+    }
+
+    /// Sorts the selections and merges any that overlap, or that are cursors at the same
+    /// place.
+    pub fn normalize(&mut self, file: &OpenFile) {
+        for sel in self.ranges.iter_mut() {
+            sel.anchor.clamp(file);
+            sel.head.clamp(file);
+        }
+        let primary = self.ranges[self.primary].clone();
+        let mut ranges: Vec<(Range<usize>, Selection)> = self.ranges.drain(..)
+            .map(|sel| (sel.range(file), sel))
+            .collect();
+        ranges.sort_by_key(|(r, _)| (r.start, r.end));
+        self.primary = 0;
+        for (range, sel) in ranges {
+            let is_primary = sel == primary;
+            if let Some(last) = self.ranges.last_mut() {
+                let last_range = last.range(file);
+                if range.start < last_range.end || range.start == last_range.start {
+                    let merged = last_range.start..range.end.max(last_range.end);
+                    let forward = file.char_idx(&last.head.coords()) >= last_range.end;
+                    if forward {
+                        last.set_range(file, merged);
+                    } else {
+                        last.set_range(file, merged);
+                        std::mem::swap(&mut last.anchor, &mut last.head);
+                    }
+                    if is_primary {
+                        self.primary = self.ranges.len() - 1;
+                    }
+                    continue;
+                }
+            }
+            if is_primary {
+                self.primary = self.ranges.len();
+            }
+            self.ranges.push(sel);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::{ops::Range, str::FromStr};
+use unicode_width::UnicodeWidthStr;
+use crate::{editor::{Editor, keymap, action::{Action, RelativeLocation}}, prompt::PromptKind};
+
+/// A piece of the status line. Segments before the first `Spacer` are drawn on the left,
+/// the rest on the right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    Mode,
+    Path,
+    Dirty,
+    Position,
+    Lines,
+    FileType,
+    LineEnding,
+    Encoding,
+    Buffer,
+    /// Keys of an unfinished sequence.
+    Keys,
+    /// The register a macro is being recorded into.
+    Recording,
+    Spacer,
+}
+
+impl Segment {
+
+    pub fn defaults() -> Vec<Segment> {
+        use Segment::*;
+        vec![Mode, Recording, Path, Dirty, Spacer, Keys, FileType, Encoding, LineEnding, Position, Lines, Buffer]
+    }
+
+    pub fn text(&self, editor: &Editor) -> String {
+        let file = editor.curr_file();
+        match self {
+            Segment::Mode => editor.mode().label().to_string(),
+            Segment::Path => match file.path().to_string_lossy() {
+                p if p.is_empty() => "[No Name]".to_string(),
+                p => p.into_owned(),
+            },
+            Segment::Dirty => if file.is_dirty() { "[+]" } else { "" }.to_string(),
+            Segment::Position => format!("{}:{}", editor.cursor().line + 1, editor.cursor_x() + 1),
+            Segment::Lines => format!("{} lines", file.len()),
+            Segment::FileType => file.filetype().to_string(),
+            Segment::LineEnding => if file.line_ending() == "\r\n" { "CRLF" } else { "LF" }.to_string(),
+            Segment::Encoding => file.encoding().to_string(),
+            Segment::Buffer => format!("[{}/{}]", editor.file_idx + 1, editor.files.len()),
+            Segment::Keys => {
+                let keys = keymap::keys_to_string(editor.pending_keys());
+                match editor.count() {
+                    Some(count) => format!("{} {}", count, keys).trim_end().to_string(),
+                    None => keys,
+                }
+            },
+            Segment::Recording => match editor.recording() {
+                Some(register) => format!("recording @{}", register),
+                None => String::new(),
+            },
+            Segment::Spacer => String::new(),
+        }
+    }
+
+    /// What clicking the segment does, if anything.
+    pub fn on_click(&self) -> Option<Action> {
+        match self {
+            Segment::Path => Some(Action::Prompt(PromptKind::Path)),
+            Segment::Position | Segment::Lines => Some(Action::Prompt(PromptKind::Command)),
+            Segment::Buffer => Some(Action::SwitchBuffer(RelativeLocation::Next)),
+            Segment::Recording => Some(Action::Record(None)),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Segment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim() {
+            "mode" => Segment::Mode,
+            "path" | "file" => Segment::Path,
+            "dirty" | "modified" => Segment::Dirty,
+            "position" | "pos" => Segment::Position,
+            "lines" => Segment::Lines,
+            "filetype" | "ft" => Segment::FileType,
+            "line_ending" | "eol" => Segment::LineEnding,
+            "encoding" => Segment::Encoding,
+            "buffer" => Segment::Buffer,
+            "keys" | "pending" => Segment::Keys,
+            "recording" | "macro" => Segment::Recording,
+            "spacer" | "|" => Segment::Spacer,
+            other => return Err(format!("unknown status segment: {}", other)),
+        })
+    }
+}
+
+/// Lays `segments` out in `width` columns, left and right groups separated by spaces.
+/// The left group is cut short when both don't fit.
+pub fn render(editor: &Editor, segments: &[Segment], width: usize) -> String {
+    layout(editor, segments, width).0
+}
+
+/// The segment drawn at column `x`.
+pub fn segment_at(editor: &Editor, segments: &[Segment], width: usize, x: usize) -> Option<Segment> {
+    layout(editor, segments, width).1.into_iter()
+        .find(|(_, cols)| cols.contains(&x))
+        .map(|(segment, _)| segment)
+}
+
+/// The rendered line, and the columns each segment in it takes up.
+fn layout(editor: &Editor, segments: &[Segment], width: usize) -> (String, Vec<(Segment, Range<usize>)>) {
+    let split = segments.iter().position(|s| *s == Segment::Spacer).unwrap_or(segments.len());
+    let join = |segments: &[Segment], spans: &mut Vec<(Segment, Range<usize>)>| {
+        let mut line = String::new();
+        let texts = segments.iter()
+            .filter(|s| **s != Segment::Spacer)
+            .map(|s| (*s, s.text(editor)))
+            .filter(|(_, t)| !t.is_empty());
+        for (segment, text) in texts {
+            if !line.is_empty() {
+                line.push_str("  ");
+            }
+            let start = line.width();
+            line.push_str(&text);
+            spans.push((segment, start..line.width()));
+        }
+        line
+    };
+    let (mut left_spans, mut right_spans) = (Vec::new(), Vec::new());
+    let left = format!(" {}", join(&segments[..split], &mut left_spans));
+    let right = format!("{} ", join(&segments[split..], &mut right_spans));
+    let room = width.saturating_sub(right.width());
+    let mut line = String::new();
+    for c in left.chars() {
+        if line.width() + c.to_string().width() > room {
+            break;
+        }
+        line.push(c);
+    }
+    let left_end = line.width();
+    let pad = width.saturating_sub(line.width() + right.width());
+    let right_start = left_end + pad;
+    let spans = left_spans.into_iter()
+        .map(|(segment, cols)| (segment, cols.start + 1..(cols.end + 1).min(left_end)))
+        .chain(right_spans.into_iter().map(|(segment, cols)| (segment, cols.start + right_start..cols.end + right_start)))
+        .filter(|(_, cols)| !cols.is_empty())
+        .collect();
+    (line + &" ".repeat(pad) + &right, spans)
+}
@@ -0,0 +1,193 @@
+use std::ops::Range;
+use crate::{buffer::Buffer, editor::{action::Target, motion}};
+
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Resolves a text object around `selection` to the char range it covers. Bare targets
+/// resolve to their inner extent; wrap them in `Target::Around` to include delimiters and
+/// surrounding whitespace.
+pub fn resolve(buffer: &Buffer, target: &Target, selection: Range<usize>) -> Option<Range<usize>> {
+    resolve_extent(buffer, target, selection, false)
+}
+
+fn resolve_extent(
+    buffer: &Buffer, target: &Target, selection: Range<usize>, around: bool,
+) -> Option<Range<usize>> {
+    let at = selection.start.min(buffer.len_chars());
+    match target {
+        Target::Inner(t) => resolve_extent(buffer, t, selection, false),
+        Target::Around(t) => resolve_extent(buffer, t, selection, true),
+        Target::Selection => Some(selection),
+        Target::Document => Some(0..buffer.len_chars()),
+        Target::Word => word(buffer, at, around),
+        Target::Line => line(buffer, buffer.char_to_line(at), around),
+        Target::Paragraph => paragraph(buffer, buffer.char_to_line(at), around),
+        Target::Parenthesis => pair(buffer, at, &BRACKETS[..1], around),
+        Target::Bracketed => pair(buffer, at, &BRACKETS, around),
+        Target::SingleQuotes => quoted(buffer, at, '\'', around),
+        Target::DoubleQuotes => quoted(buffer, at, '"', around),
+        Target::Backticks => quoted(buffer, at, '`', around),
+        Target::Char(c) => match BRACKETS.iter().find(|(o, cl)| o == c || cl == c) {
+            Some(p) => pair(buffer, at, std::slice::from_ref(p), around),
+            None => quoted(buffer, at, *c, around),
+        },
+        Target::InputWord(word) => {
+            let len = word.chars().count();
+            let start = buffer.find(word, at.saturating_sub(len.saturating_sub(1)))?;
+            Some(start..start + len)
+        },
+        Target::Region(first, last) => {
+            let last_line = buffer.len_lines().saturating_sub(1);
+            let (first, last) = ((*first as usize).min(last_line), (*last as usize).min(last_line));
+            let end = if last == last_line { buffer.len_chars() } else { buffer.line_to_char(last + 1) };
+            Some(buffer.line_to_char(first.min(last))..end)
+        },
+    }
+}
+
+#[derive(PartialEq)]
+pub enum Class { Word, Blank, Punct, Newline }
+
+pub fn class(c: char) -> Class {
+    match c {
+        '\n' | '\r' => Class::Newline,
+        c if c.is_alphanumeric() || c == '_' => Class::Word,
+        c if c.is_whitespace() => Class::Blank,
+        _ => Class::Punct,
+    }
+}
+
+fn word(buffer: &Buffer, at: usize, around: bool) -> Option<Range<usize>> {
+    let len = buffer.len_chars();
+    let at = if at == len || class(buffer.char(at)) == Class::Newline {
+        at.checked_sub(1).filter(|&i| class(buffer.char(i)) != Class::Newline)?
+    } else {
+        at
+    };
+    let kind = class(buffer.char(at));
+    let (mut start, mut end) = (at, at + 1);
+    while start > 0 && class(buffer.char(start - 1)) == kind {
+        start -= 1;
+    }
+    while end < len && class(buffer.char(end)) == kind {
+        end += 1;
+    }
+    if around && kind != Class::Blank {
+        let trailing = end;
+        while end < len && class(buffer.char(end)) == Class::Blank {
+            end += 1;
+        }
+        if end == trailing {
+            while start > 0 && class(buffer.char(start - 1)) == Class::Blank {
+                start -= 1;
+            }
+        }
+    }
+    Some(start..end)
+}
+
+fn line(buffer: &Buffer, line: usize, around: bool) -> Option<Range<usize>> {
+    let start = buffer.line_to_char(line);
+    if around {
+        let end = if line + 1 < buffer.len_lines() { buffer.line_to_char(line + 1) } else { buffer.len_chars() };
+        return Some(start..end);
+    }
+    let end = start + buffer.line_len(line);
+    let mut first = start;
+    while first < end && class(buffer.char(first)) == Class::Blank {
+        first += 1;
+    }
+    let mut last = end;
+    while last > first && class(buffer.char(last - 1)) == Class::Blank {
+        last -= 1;
+    }
+    Some(first..last)
+}
+
+/// The run of non-blank lines around `line`, or of blank lines if `line` is blank. Around
+/// also takes the blank lines that follow.
+fn paragraph(buffer: &Buffer, line: usize, around: bool) -> Option<Range<usize>> {
+    let last_line = buffer.len_lines() - 1;
+    let blank = motion::is_blank(buffer, line);
+    let (mut first, mut last) = (line, line);
+    while first > 0 && motion::is_blank(buffer, first - 1) == blank {
+        first -= 1;
+    }
+    while last < last_line && motion::is_blank(buffer, last + 1) == blank {
+        last += 1;
+    }
+    if around && !blank {
+        while last < last_line && motion::is_blank(buffer, last + 1) {
+            last += 1;
+        }
+    }
+    let end = if last == last_line { buffer.len_chars() } else { buffer.line_to_char(last + 1) };
+    Some(buffer.line_to_char(first)..end)
+}
+
+/// The innermost bracket pair enclosing `at`, or the pair whose bracket `at` is on.
+fn pair(buffer: &Buffer, at: usize, pairs: &[(char, char)], around: bool) -> Option<Range<usize>> {
+    let c = buffer.get_char(at);
+    let open = if pairs.iter().any(|(o, _)| Some(*o) == c) {
+        at
+    } else if let Some(p) = pairs.iter().find(|(_, cl)| Some(*cl) == c) {
+        scan_back(buffer, at, std::slice::from_ref(p))?
+    } else {
+        scan_back(buffer, at, pairs)?
+    };
+    let kind = pairs.iter().find(|(o, _)| *o == buffer.char(open))?;
+    let close = scan_forward(buffer, open, std::slice::from_ref(kind))?;
+    Some(if around { open..close + 1 } else { open + 1..close })
+}
+
+fn scan_back(buffer: &Buffer, from: usize, pairs: &[(char, char)]) -> Option<usize> {
+    let mut stack = Vec::new();
+    for i in (0..from).rev() {
+        let c = buffer.char(i);
+        if let Some(&(_, close)) = pairs.iter().find(|(_, cl)| *cl == c) {
+            stack.push(close);
+        } else if let Some(&(_, close)) = pairs.iter().find(|(o, _)| *o == c) {
+            match stack.last() {
+                None => return Some(i),
+                Some(&top) if top == close => { stack.pop(); },
+                Some(_) => {},
+            }
+        }
+    }
+    None
+}
+
+fn scan_forward(buffer: &Buffer, open: usize, pairs: &[(char, char)]) -> Option<usize> {
+    let (o, cl) = pairs[0];
+    let mut depth = 0;
+    for i in open + 1..buffer.len_chars() {
+        match buffer.char(i) {
+            c if c == o => depth += 1,
+            c if c == cl && depth == 0 => return Some(i),
+            c if c == cl => depth -= 1,
+            _ => {},
+        }
+    }
+    None
+}
+
+/// The quoted string on `at`'s line that contains `at`, or else the next one after it.
+/// Quotes escaped with a backslash are skipped.
+fn quoted(buffer: &Buffer, at: usize, quote: char, around: bool) -> Option<Range<usize>> {
+    let line = buffer.char_to_line(at);
+    let start = buffer.line_to_char(line);
+    let end = start + buffer.line_len(line);
+    let mut quotes = Vec::new();
+    let mut escaped = false;
+    for i in start..end {
+        let c = buffer.char(i);
+        if c == quote && !escaped {
+            quotes.push(i);
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    let (open, close) = quotes.chunks_exact(2)
+        .map(|q| (q[0], q[1]))
+        .find(|&(_, close)| at <= close)?;
+    Some(if around { open..close + 1 } else { open + 1..close })
+}
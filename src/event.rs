@@ -1 +1,5 @@
 pub mod key;
+pub mod mouse;
+pub mod channel;
+pub mod process;
+pub mod watch;
@@ -0,0 +1,143 @@
+use std::{
+    collections::VecDeque, path::PathBuf,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+    time::Instant,
+};
+use crossterm::event::Event;
+use crate::event::process::Output;
+
+/// Everything the editor waits on. Background threads send these down one channel, so
+/// none of them can hold up typing.
+#[derive(Debug)]
+pub enum Message {
+    /// Terminal input.
+    Input(Event),
+    /// The terminal has no more input.
+    Closed,
+    Timer(Timer),
+    /// A watched file was modified, created or removed.
+    FileChanged(PathBuf),
+    /// Output from a child process, by the id it was started with.
+    Process(usize, Output),
+    /// A message from a language server, by the server's name.
+    Lsp(String, serde_json::Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timer {
+    /// The wait for the next key of a sequence is over.
+    Keys,
+    /// The message in the bottom line has expired.
+    Message,
+}
+
+/// The editor's end of the channel, and the timers it has set.
+#[derive(Debug)]
+pub struct Events {
+    sender: Sender<Message>,
+    receiver: Receiver<Message>,
+    timers: Vec<(Timer, Instant)>,
+    /// Messages taken out of turn, to be handed out again first.
+    deferred: VecDeque<Message>,
+}
+
+impl Events {
+
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { sender, receiver, timers: Vec::new(), deferred: VecDeque::new() }
+    }
+
+    /// A sender for a background thread to report to the editor with.
+    pub fn sender(&self) -> Sender<Message> {
+        self.sender.clone()
+    }
+
+    /// Fires `timer` at `at`, replacing when it was set to fire before.
+    pub fn set_timer(&mut self, timer: Timer, at: Instant) {
+        self.cancel_timer(timer);
+        self.timers.push((timer, at));
+    }
+
+    pub fn cancel_timer(&mut self, timer: Timer) {
+        self.timers.retain(|(t, _)| *t != timer);
+    }
+
+    /// Hands `message` out again before anything else.
+    pub fn defer(&mut self, message: Message) {
+        self.deferred.push_back(message);
+    }
+
+    /// The next message, waiting for one as long as it takes.
+    pub fn wait(&mut self) -> Message {
+        loop {
+            if let Some(message) = self.next_before(None) {
+                return message;
+            }
+        }
+    }
+
+    /// The next message, if there is one already or a timer is due.
+    pub fn try_next(&mut self) -> Option<Message> {
+        self.next_before(Some(Instant::now()))
+    }
+
+    /// The next message, waiting until `deadline` at the latest.
+    pub fn next_before(&mut self, deadline: Option<Instant>) -> Option<Message> {
+        if let Some(message) = self.deferred.pop_front() {
+            return Some(message);
+        }
+        if let Some(timer) = self.due_timer() {
+            return Some(Message::Timer(timer));
+        }
+        let until = self.timers.iter().map(|(_, at)| *at).chain(deadline).min();
+        let received = match until {
+            Some(until) => match self.receiver.recv_timeout(until.saturating_duration_since(Instant::now())) {
+                Err(RecvTimeoutError::Timeout) => None,
+                received => received.ok(),
+            },
+            None => self.receiver.recv().ok(),
+        };
+        received.or_else(|| self.due_timer().map(Message::Timer))
+    }
+
+    /// The next terminal event before `deadline`. Other messages are kept for later.
+    pub fn next_input(&mut self, deadline: Instant) -> Option<Event> {
+        let mut skipped = Vec::new();
+        let event = loop {
+            let message = match self.receiver.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Disconnected) => break None,
+                Err(TryRecvError::Empty) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    match self.receiver.recv_timeout(left) {
+                        Ok(message) => message,
+                        Err(_) => break None,
+                    }
+                },
+            };
+            match message {
+                Message::Input(event) => break Some(event),
+                Message::Closed => {
+                    skipped.push(Message::Closed);
+                    break None;
+                },
+                other => skipped.push(other),
+            }
+        };
+        self.deferred.extend(skipped);
+        event
+    }
+
+    fn due_timer(&mut self) -> Option<Timer> {
+        let now = Instant::now();
+        let idx = self.timers.iter().position(|(_, at)| *at <= now)?;
+        Some(self.timers.remove(idx).0)
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::{Duration, Instant};
+
+/// How soon a click must follow the last one to count as a double or triple click.
+const MULTI_CLICK: Duration = Duration::from_millis(400);
+
+/// What a click selects, and a drag from it extends by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Char,
+    Word,
+    Line,
+}
+
+/// Counts clicks in the same place in quick succession, so that a second one selects a
+/// word and a third a line. A fourth starts over.
+#[derive(Debug, Clone, Default)]
+pub struct Clicks {
+    last: Option<((u16, u16), Instant)>,
+    count: usize,
+}
+
+impl Clicks {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a click at `at`, returning what it selects.
+    pub fn press(&mut self, at: (u16, u16)) -> Unit {
+        let now = Instant::now();
+        self.count = match self.last {
+            Some((last, time)) if last == at && now.duration_since(time) < MULTI_CLICK => self.count % 3 + 1,
+            _ => 1,
+        };
+        self.last = Some((at, now));
+        match self.count {
+            1 => Unit::Char,
+            2 => Unit::Word,
+            _ => Unit::Line,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    process::{Command, Stdio},
+    sync::mpsc::Sender,
+    thread,
+};
+use crate::event::channel::Message;
+
+/// What a child process sends back to the editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    Stdout(String),
+    Stderr(String),
+    /// The process ended, with its exit code if it had one.
+    Exit(Option<i32>),
+}
+
+/// Runs `command` through the shell, sending its output a line at a time as
+/// `Message::Process(id, ..)`, and then its exit.
+pub fn spawn(id: usize, command: &str, sender: Sender<Message>) -> io::Result<()> {
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".into());
+    let mut child = Command::new(shell)
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().map(|out| forward(out, id, Output::Stdout, sender.clone()));
+    let stderr = child.stderr.take().map(|err| forward(err, id, Output::Stderr, sender.clone()));
+    thread::spawn(move || {
+        // Every line is sent before the exit.
+        for reader in stdout.into_iter().chain(stderr) {
+            let _ = reader.join();
+        }
+        let code = child.wait().ok().and_then(|status| status.code());
+        let _ = sender.send(Message::Process(id, Output::Exit(code)));
+    });
+    Ok(())
+}
+
+fn forward<R: Read + Send + 'static>(
+    from: R, id: usize, wrap: fn(String) -> Output, sender: Sender<Message>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(from).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if sender.send(Message::Process(id, wrap(line))).is_err() {
+                break;
+            }
+        }
+    })
+}
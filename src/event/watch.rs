@@ -0,0 +1,68 @@
+use std::{
+    collections::HashMap, fs, path::{Path, PathBuf},
+    sync::{Arc, Mutex, mpsc::Sender},
+    thread, time::{Duration, SystemTime},
+};
+use crate::event::channel::Message;
+
+/// How often watched files are checked.
+const INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches files for changes on disk from a thread of its own, sending
+/// `Message::FileChanged` when one's modification time changes or it appears or goes.
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    files: Arc<Mutex<HashMap<PathBuf, Option<SystemTime>>>>,
+}
+
+impl Watcher {
+
+    /// Starts the thread, which runs until the watcher is dropped or the editor's end of
+    /// the channel is gone.
+    pub fn new(sender: Sender<Message>) -> Self {
+        let files: Arc<Mutex<HashMap<PathBuf, Option<SystemTime>>>> = Arc::default();
+        let weak = Arc::downgrade(&files);
+        thread::spawn(move || loop {
+            thread::sleep(INTERVAL);
+            let watched = match weak.upgrade() {
+                Some(watched) => watched,
+                None => return,
+            };
+            let changed: Vec<PathBuf> = match watched.lock() {
+                Ok(mut files) => files.iter_mut()
+                    .filter_map(|(path, last)| {
+                        let modified = modified(path);
+                        if modified == *last {
+                            return None;
+                        }
+                        *last = modified;
+                        Some(path.clone())
+                    })
+                    .collect(),
+                Err(_) => return,
+            };
+            for path in changed {
+                if sender.send(Message::FileChanged(path)).is_err() {
+                    return;
+                }
+            }
+        });
+        Self { files }
+    }
+
+    pub fn watch(&self, path: &Path) {
+        if let Ok(mut files) = self.files.lock() {
+            files.insert(path.to_path_buf(), modified(path));
+        }
+    }
+
+    pub fn unwatch(&self, path: &Path) {
+        if let Ok(mut files) = self.files.lock() {
+            files.remove(path);
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -1,39 +1,406 @@
-use std::{fs, io, path::PathBuf};
-use crate::editor::row::Row;
+pub mod history;
+
+use std::{fs, io::{self, Write}, ops::Range, path::{Path, PathBuf}, process, time::SystemTime};
+use crate::{buffer::Buffer, editor::{Coords, row::Row, selection::Selections}};
+use history::{Edit, History};
 
 #[derive(Debug)]
 pub struct OpenFile {
-    rows: Vec<Row>,
+    buffer: Buffer,
     path: PathBuf,
+    line_ending: &'static str,
+    modified: Option<SystemTime>,
+    history: History,
+    saved: usize,
+    undo_file: bool,
+    /// Where the selections were when another file was switched to.
+    selections: Selections,
+    scratch: bool,
 }
 
 impl OpenFile {
 
     pub fn new<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
         let p = path.into();
-        let file = fs::read_to_string(&p)?;
-        let mut rows = Vec::new();
-        for line in file.lines() {
-            rows.push(Row::from(line));
+        let file = fs::File::open(&p)?;
+        let modified = file.metadata()?.modified().ok();
+        let buffer = Buffer::from_reader(io::BufReader::new(file))?;
+        let line_ending = match buffer.line(0) {
+            Some(l) if buffer.len_lines() > 1
+                && buffer.slice(l.len_chars()..buffer.line_to_char(1)) == "\r\n" => "\r\n",
+            _ => "\n",
+        };
+        Ok ( Self { buffer, path: p, line_ending, modified, ..Default::default() } )
+    }
+
+    pub fn with_path<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into(), ..Default::default() }
+    }
+
+    /// Opens the file at `path`, or an empty one that will be created there on save if
+    /// nothing exists yet. Any other failure to read it is returned, naming the path.
+    pub fn open_or_new<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let p = path.into();
+        match Self::new(&p) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::with_path(p)),
+            Err(e) => Err(io::Error::new(e.kind(), format!("{}: {}", p.display(), e))),
+            file => file,
         }
-        Ok ( Self { rows, path: p } )
     }
 
-    pub fn get(&self, idx: usize) -> Option<&Row> {
-        self.rows.get(idx as usize)
+    /// Keeps `selections` with the file while another one is current.
+    pub fn stash_selections(&mut self, selections: Selections) {
+        self.selections = selections;
+    }
+
+    /// The selections the file was left with, pulled back inside the document in case it
+    /// changed since.
+    pub fn take_selections(&mut self) -> Selections {
+        let mut selections = std::mem::take(&mut self.selections);
+        selections.normalize(self);
+        selections
+    }
+
+    /// A buffer holding `text` that isn't backed by a file and can't be saved.
+    pub fn scratch(name: &str, text: &str) -> Self {
+        Self { buffer: Buffer::from(text), path: name.into(), scratch: true, ..Default::default() }
+    }
+
+    pub fn is_scratch(&self) -> bool {
+        self.scratch
+    }
+
+    pub fn get(&self, idx: usize) -> Option<Row<'_>> {
+        self.buffer.line(idx).map(Row::from)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.rows.is_empty()
+        self.buffer.is_empty()
     }
 
     pub fn len(&self) -> usize {
-        self.rows.len()
+        self.buffer.len_lines()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.scratch && self.history.current() != self.saved
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Keeps the undo history in a hidden file next to this one, loading any history
+    /// already stored there for the current contents.
+    pub fn set_undo_file(&mut self, enabled: bool) {
+        self.undo_file = enabled && !self.scratch;
+        if self.undo_file && self.history.is_empty() {
+            if let Some(history) = self.undo_path().and_then(|p| History::load(&p, &self.buffer).ok()) {
+                self.saved = history.current();
+                self.history = history;
+            }
+        }
+    }
+
+    fn undo_path(&self) -> Option<PathBuf> {
+        let name = self.path.file_name()?.to_string_lossy();
+        Some(self.path.with_file_name(format!(".{}.ded-undo", name)))
+    }
+
+    fn apply(&mut self, edit: Edit) -> usize {
+        edit.apply(&mut self.buffer);
+        let cursor = edit.cursor();
+        self.history.record(edit);
+        cursor
+    }
+
+    /// Ends the current undo group, e.g. when the cursor moves away from it.
+    pub fn seal_history(&mut self) {
+        self.history.seal();
+    }
+
+    /// Records every edit until `end_batch` as a single undo step.
+    pub fn begin_batch(&mut self) {
+        self.history.begin_batch();
+    }
+
+    pub fn end_batch(&mut self) {
+        self.history.end_batch();
+    }
+
+    pub fn undo(&mut self) -> Option<Coords> {
+        self.history.undo(&mut self.buffer).map(|idx| self.coords(idx))
+    }
+
+    pub fn redo(&mut self) -> Option<Coords> {
+        self.history.redo(&mut self.buffer).map(|idx| self.coords(idx))
+    }
+
+    pub fn earlier(&mut self) -> Option<Coords> {
+        self.history.earlier(&mut self.buffer).map(|idx| self.coords(idx))
+    }
+
+    pub fn later(&mut self) -> Option<Coords> {
+        self.history.later(&mut self.buffer).map(|idx| self.coords(idx))
+    }
+
+    /// Whether the file on disk was modified, created or replaced since it was loaded or saved.
+    pub fn changed_on_disk(&self) -> bool {
+        let on_disk = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        match (self.modified, on_disk) {
+            (Some(loaded), Some(current)) => loaded != current,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        }
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        if self.changed_on_disk() {
+            return Err(io::Error::other(
+                format!("{} changed on disk since it was loaded", self.path.display())));
+        }
+        self.write()
+    }
+
+    /// Saves even if the file on disk changed since it was loaded.
+    pub fn force_save(&mut self) -> io::Result<()> {
+        self.write()
+    }
+
+    /// Saves to `path` and makes it the file's new path. Refuses to replace an existing
+    /// file unless `force` is set.
+    pub fn save_as<P: Into<PathBuf>>(&mut self, path: P, force: bool) -> io::Result<()> {
+        let path = path.into();
+        if path == self.path {
+            return if force { self.force_save() } else { self.save() };
+        }
+        if !force && path.exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display())));
+        }
+        let old_path = std::mem::replace(&mut self.path, path);
+        if let Err(e) = self.write() {
+            self.path = old_path;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Replaces the file on disk with the buffer and marks the current revision as saved.
+    fn write(&mut self) -> io::Result<()> {
+        if self.scratch {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("{} is a scratch buffer", self.path.display())));
+        }
+        if self.path.as_os_str().is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no file name"));
+        }
+        let target = fs::canonicalize(&self.path).unwrap_or_else(|_| self.path.clone());
+        replace(&target, |out| self.buffer.write_to(out))?;
+        self.modified = fs::metadata(&target).and_then(|m| m.modified()).ok();
+        self.history.seal();
+        self.saved = self.history.current();
+        if self.undo_file {
+            if let Some(undo_path) = self.undo_path() {
+                self.history.save(&undo_path, &self.buffer)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn line_ending(&self) -> &'static str {
+        self.line_ending
+    }
+
+    /// The file's type, going by its extension or name.
+    pub fn filetype(&self) -> &'static str {
+        let name = self.path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        let ext = self.path.extension().map(|e| e.to_string_lossy()).unwrap_or_default();
+        match (name.as_ref(), ext.as_ref()) {
+            ("Makefile", _) | ("makefile", _) => "make",
+            ("Dockerfile", _) => "dockerfile",
+            (_, "rs") => "rust",
+            (_, "toml") => "toml",
+            (_, "md") | (_, "markdown") => "markdown",
+            (_, "c") | (_, "h") => "c",
+            (_, "cpp") | (_, "cc") | (_, "hpp") => "cpp",
+            (_, "go") => "go",
+            (_, "py") => "python",
+            (_, "js") | (_, "mjs") => "javascript",
+            (_, "ts") => "typescript",
+            (_, "json") => "json",
+            (_, "yaml") | (_, "yml") => "yaml",
+            (_, "html") | (_, "htm") => "html",
+            (_, "css") => "css",
+            (_, "sh") | (_, "bash") | (_, "zsh") => "sh",
+            (_, "lua") => "lua",
+            _ => "text",
+        }
+    }
+
+    /// Buffers are always UTF-8; a byte order mark read from the file is kept in the text.
+    pub fn encoding(&self) -> &'static str {
+        if self.buffer.get_char(0) == Some('\u{feff}') { "utf-8-bom" } else { "utf-8" }
+    }
+
+    pub fn insert(&mut self, at: usize, text: &str) -> usize {
+        if text.is_empty() {
+            return at;
+        }
+        self.apply(Edit::Insert { at, text: text.to_string() })
+    }
+
+    pub fn split_line(&mut self, at: usize) -> usize {
+        let line_ending = self.line_ending();
+        self.insert(at, line_ending)
+    }
+
+    pub fn open_line(&mut self, line: usize, below: bool) -> usize {
+        let line = line.min(self.len().saturating_sub(1));
+        let line_ending = self.line_ending();
+        if below {
+            let end = self.buffer.line_to_char(line) + self.buffer.line_len(line);
+            self.insert(end, line_ending)
+        } else {
+            let start = self.buffer.line_to_char(line);
+            self.insert(start, line_ending);
+            start
+        }
+    }
+
+    /// Deletes `n` graphemes before `at`, joining with the previous row at column zero.
+    pub fn delete_back(&mut self, at: usize, n: usize) -> usize {
+        let mut start = at;
+        for _ in 0..n {
+            start = self.buffer.prev_grapheme_boundary(start);
+        }
+        self.remove(start..at)
+    }
+
+    /// Deletes `n` graphemes after `at`, joining with the next row at the end of a line.
+    pub fn delete_forward(&mut self, at: usize, n: usize) -> usize {
+        let mut end = at;
+        for _ in 0..n {
+            end = self.buffer.next_grapheme_boundary(end);
+        }
+        self.remove(at..end)
+    }
+
+    pub fn remove(&mut self, chars: Range<usize>) -> usize {
+        if chars.is_empty() {
+            return chars.start;
+        }
+        let text = self.buffer.slice(chars.clone()).to_string();
+        self.apply(Edit::Delete { at: chars.start, text })
+    }
+
+    /// Moves lines `first..=last` up (negative `by`) or down, returning the char index
+    /// the block now starts at.
+    pub fn move_lines(&mut self, first: usize, last: usize, by: isize) -> usize {
+        let count = self.len();
+        let last = last.min(count - 1);
+        let first = first.min(last);
+        let max = count - 1 - (last - first);
+        let dest = (first as isize + by).clamp(0, max as isize) as usize;
+        let start = self.buffer.line_to_char(first);
+        if dest == first {
+            return start;
+        }
+        let end = self.buffer.line_to_char(last) + self.buffer.line_len(last);
+        let block = self.buffer.slice(start..end).to_string();
+        if last + 1 < count {
+            self.remove(start..self.buffer.line_to_char(last + 1));
+        } else {
+            let prev_end = self.buffer.line_to_char(first - 1) + self.buffer.line_len(first - 1);
+            self.remove(prev_end..end);
+        }
+        let line_ending = self.line_ending();
+        if dest < self.len() {
+            let at = self.buffer.line_to_char(dest);
+            self.insert(at, &format!("{}{}", block, line_ending));
+            at
+        } else {
+            let at = self.buffer.len_chars();
+            self.insert(at, &format!("{}{}", line_ending, block));
+            self.buffer.len_chars() - block.chars().count()
+        }
+    }
+
+    /// Moves a range of text `by` graphemes to the left (negative) or right, returning the
+    /// char index of its new end.
+    pub fn move_range(&mut self, chars: Range<usize>, by: isize) -> usize {
+        let text = self.buffer.slice(chars.clone()).to_string();
+        let mut at = self.remove(chars);
+        for _ in 0..by.unsigned_abs() {
+            at = if by < 0 {
+                self.buffer.prev_grapheme_boundary(at)
+            } else {
+                self.buffer.next_grapheme_boundary(at)
+            };
+        }
+        self.insert(at, &text)
+    }
+
+    pub fn char_idx(&self, at: &Coords) -> usize {
+        self.buffer.grapheme_to_char(at.y, at.x)
+    }
+
+    pub fn coords(&self, char_idx: usize) -> Coords {
+        let (y, x) = self.buffer.char_to_grapheme(char_idx);
+        Coords { x, y }
     }
 }
 
 impl Default for OpenFile {
     fn default() -> Self {
-        Self::new("/home/chrisp/div/srv/src/app.rs").unwrap()
+        Self {
+            buffer: Buffer::new(),
+            path: PathBuf::new(),
+            line_ending: "\n",
+            modified: None,
+            history: History::new(),
+            saved: 0,
+            undo_file: false,
+            selections: Selections::default(),
+            scratch: false,
+        }
+    }
+}
+
+/// Writes a temporary file next to `target` with `write` and renames it over the original,
+/// so a failed write never leaves a truncated file behind.
+pub(crate) fn replace<F>(target: &Path, write: F) -> io::Result<()>
+where F: FnOnce(&mut io::BufWriter<fs::File>) -> io::Result<()>
+{
+    let name = target.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?
+        .to_string_lossy();
+    let tmp = target.with_file_name(format!(".{}.{}.tmp", name, process::id()));
+    let res = write_tmp(&tmp, target, write)
+        .and_then(|()| fs::rename(&tmp, target));
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    res
+}
+
+fn write_tmp<F>(tmp: &Path, target: &Path, write: F) -> io::Result<()>
+where F: FnOnce(&mut io::BufWriter<fs::File>) -> io::Result<()>
+{
+    let file = fs::File::create(tmp)?;
+    if let Ok(meta) = fs::metadata(target) {
+        file.set_permissions(meta.permissions())?;
     }
+    let mut out = io::BufWriter::new(file);
+    write(&mut out)?;
+    out.flush()?;
+    out.get_ref().sync_all()
 }
@@ -0,0 +1,306 @@
+use std::{fs, io, path::Path};
+use serde::{Serialize, Deserialize};
+use crate::buffer::Buffer;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Edit {
+    Insert { at: usize, text: String },
+    Delete { at: usize, text: String },
+}
+
+impl Edit {
+
+    pub fn apply(&self, buffer: &mut Buffer) {
+        match self {
+            Edit::Insert { at, text } => buffer.insert(*at, text),
+            Edit::Delete { at, text } => buffer.remove(*at..*at + text.chars().count()),
+        }
+    }
+
+    pub fn invert(&self) -> Edit {
+        match self.clone() {
+            Edit::Insert { at, text } => Edit::Delete { at, text },
+            Edit::Delete { at, text } => Edit::Insert { at, text },
+        }
+    }
+
+    /// Char index the cursor should land on once this edit has been applied.
+    pub fn cursor(&self) -> usize {
+        match self {
+            Edit::Insert { at, text } => at + text.chars().count(),
+            Edit::Delete { at, .. } => *at,
+        }
+    }
+
+    /// Folds `next` into `self` when it continues the same run of typing or deleting.
+    fn merge(&mut self, next: &Edit) -> bool {
+        match (self, next) {
+            (Edit::Insert { at, text }, Edit::Insert { at: n_at, text: n_text }) => {
+                if *n_at != *at + text.chars().count() || ends_group(text) {
+                    return false;
+                }
+                text.push_str(n_text);
+            },
+            (Edit::Delete { at, text }, Edit::Delete { at: n_at, text: n_text }) => {
+                if *n_at == *at {
+                    text.push_str(n_text);
+                } else if *n_at + n_text.chars().count() == *at {
+                    text.insert_str(0, n_text);
+                    *at = *n_at;
+                } else {
+                    return false;
+                }
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+fn ends_group(text: &str) -> bool {
+    text.ends_with(['\n', '\r'])
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    parent: usize,
+    last_child: Option<usize>,
+    edits: Vec<Edit>,
+}
+
+/// Undo tree for one file. Undoing moves to the parent revision; making a new edit after
+/// undoing starts a new branch instead of discarding the old one, and redo follows the
+/// branch that was visited last.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct History {
+    revisions: Vec<Revision>,
+    current: usize,
+    #[serde(skip)]
+    open: bool,
+    #[serde(skip)]
+    batch: Option<bool>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            revisions: vec![Revision { parent: 0, last_child: None, edits: Vec::new() }],
+            current: 0,
+            open: false,
+            batch: None,
+        }
+    }
+}
+
+impl History {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn len(&self) -> usize {
+        self.revisions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.revisions.len() == 1
+    }
+
+    /// Records an applied edit, grouping it with the previous one while the group is open.
+    pub fn record(&mut self, edit: Edit) {
+        let in_batch = self.batch == Some(true);
+        if self.open || in_batch {
+            let revision = &mut self.revisions[self.current];
+            if let Some(last) = revision.edits.last_mut() {
+                if self.open && last.merge(&edit) {
+                    return;
+                }
+            }
+            if in_batch {
+                revision.edits.push(edit);
+                return;
+            }
+        }
+        let idx = self.revisions.len();
+        self.revisions.push(Revision {
+            parent: self.current, last_child: None, edits: vec![edit]
+        });
+        self.revisions[self.current].last_child = Some(idx);
+        self.current = idx;
+        self.open = true;
+        if self.batch.is_some() {
+            self.batch = Some(true);
+        }
+    }
+
+    pub fn begin_batch(&mut self) {
+        self.batch = Some(false);
+    }
+
+    pub fn end_batch(&mut self) {
+        self.batch = None;
+    }
+
+    /// Ends the current group so the next edit becomes its own undo step.
+    pub fn seal(&mut self) {
+        self.open = false;
+    }
+
+    pub fn undo(&mut self, buffer: &mut Buffer) -> Option<usize> {
+        self.seal();
+        if self.current == 0 {
+            return None;
+        }
+        let revision = &self.revisions[self.current];
+        let mut cursor = None;
+        for edit in revision.edits.iter().rev() {
+            let inverse = edit.invert();
+            inverse.apply(buffer);
+            cursor = Some(inverse.cursor());
+        }
+        let parent = revision.parent;
+        self.revisions[parent].last_child = Some(self.current);
+        self.current = parent;
+        cursor
+    }
+
+    pub fn redo(&mut self, buffer: &mut Buffer) -> Option<usize> {
+        self.seal();
+        let child = self.revisions[self.current].last_child?;
+        let mut cursor = None;
+        for edit in self.revisions[child].edits.iter() {
+            edit.apply(buffer);
+            cursor = Some(edit.cursor());
+        }
+        self.current = child;
+        cursor
+    }
+
+    /// Moves to the revision made just before the current one, across branches.
+    pub fn earlier(&mut self, buffer: &mut Buffer) -> Option<usize> {
+        let target = self.current.checked_sub(1)?;
+        self.goto(target, buffer)
+    }
+
+    /// Moves to the revision made just after the current one, across branches.
+    pub fn later(&mut self, buffer: &mut Buffer) -> Option<usize> {
+        if self.current + 1 >= self.revisions.len() {
+            return None;
+        }
+        self.goto(self.current + 1, buffer)
+    }
+
+    /// Walks the tree from the current revision to `target`, undoing up to the common
+    /// ancestor and redoing down the target's branch.
+    pub fn goto(&mut self, target: usize, buffer: &mut Buffer) -> Option<usize> {
+        if target >= self.revisions.len() || target == self.current {
+            return None;
+        }
+        let target_path = self.path_to_root(target);
+        let mut cursor = None;
+        while !target_path.contains(&self.current) {
+            cursor = self.undo(buffer).or(cursor);
+        }
+        let down = target_path.iter().position(|&r| r == self.current).unwrap_or(0);
+        for &rev in target_path[..down].iter().rev() {
+            self.revisions[self.current].last_child = Some(rev);
+            cursor = self.redo(buffer).or(cursor);
+        }
+        cursor
+    }
+
+    fn path_to_root(&self, mut rev: usize) -> Vec<usize> {
+        let mut path = vec![rev];
+        while rev != 0 {
+            rev = self.revisions[rev].parent;
+            path.push(rev);
+        }
+        path
+    }
+
+    /// Loads a history written by `save`, as long as it was written for `buffer`'s contents.
+    pub fn load(path: &Path, buffer: &Buffer) -> io::Result<Self> {
+        let stored: Stored = serde_json::from_reader(io::BufReader::new(fs::File::open(path)?))?;
+        if stored.hash != hash(buffer) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "undo file does not match"));
+        }
+        stored.history.check(buffer)?;
+        Ok(stored.history)
+    }
+
+    /// Makes sure the tree read from disk links up: every parent comes before its child,
+    /// every index is in range and every edit fits the text it is replayed on, so walking
+    /// it can't panic or loop.
+    fn check(&self, buffer: &Buffer) -> io::Result<()> {
+        let corrupt = || io::Error::new(io::ErrorKind::InvalidData, "undo file is corrupt");
+        let len = self.revisions.len();
+        let linked = len > 0 && self.current < len && self.revisions.iter().enumerate().all(|(idx, rev)| {
+            (rev.parent < idx || (idx == 0 && rev.parent == 0))
+                && rev.last_child.is_none_or(|child| child > idx && child < len && self.revisions[child].parent == idx)
+        });
+        if !linked {
+            return Err(corrupt());
+        }
+        // The buffer holds the current revision, so undoing the edits on its path gives
+        // the length of the root; every revision is then replayed from its parent's length.
+        let mut root_len = Some(buffer.len_chars());
+        for rev in self.path_to_root(self.current) {
+            if rev == 0 {
+                break;
+            }
+            for edit in self.revisions[rev].edits.iter().rev() {
+                root_len = root_len.and_then(|chars| match edit {
+                    Edit::Insert { text, .. } => chars.checked_sub(text.chars().count()),
+                    Edit::Delete { text, .. } => chars.checked_add(text.chars().count()),
+                });
+            }
+        }
+        let mut lens = vec![root_len.ok_or_else(corrupt)?];
+        for rev in &self.revisions[1..] {
+            let mut chars = Some(lens[rev.parent]);
+            for edit in &rev.edits {
+                chars = chars.and_then(|chars| match edit {
+                    Edit::Insert { at, text } if *at <= chars => chars.checked_add(text.chars().count()),
+                    Edit::Delete { at, text } => at.checked_add(text.chars().count())
+                        .filter(|&end| end <= chars)
+                        .map(|end| chars - (end - at)),
+                    _ => None,
+                });
+            }
+            lens.push(chars.ok_or_else(corrupt)?);
+        }
+        if lens[self.current] != buffer.len_chars() {
+            return Err(corrupt());
+        }
+        Ok(())
+    }
+
+    /// Writes through a temporary file, so a crash while saving keeps the old undo file.
+    pub fn save(&self, path: &Path, buffer: &Buffer) -> io::Result<()> {
+        let stored = StoredRef { hash: hash(buffer), history: self };
+        super::replace(path, |out| Ok(serde_json::to_writer(out, &stored)?))
+    }
+}
+
+#[derive(Deserialize)]
+struct Stored {
+    hash: u64,
+    history: History,
+}
+
+#[derive(Serialize)]
+struct StoredRef<'a> {
+    hash: u64,
+    history: &'a History,
+}
+
+/// FNV-1a over the buffer, so undo files stay valid across builds.
+fn hash(buffer: &Buffer) -> u64 {
+    buffer.chunks().flat_map(str::bytes).fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
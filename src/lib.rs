@@ -1,12 +1,19 @@
+pub mod buffer;
 pub mod file;
 pub mod editor;
 pub mod term;
 pub mod event;
 pub mod prompt;
+pub mod crash;
 
 use editor::Editor;
 
-pub fn run() -> crossterm::Result<()> {
-     Editor::default().run()
+/// Runs the editor on the files named on the command line. However it stops, the
+/// terminal is restored, and unless the user quit, unsaved changes are written to the
+/// recovery directory.
+pub fn run() -> term::TermResult<()> {
+    crash::install_hook();
+    let mut editor = Editor::from_args()?;
+    crash::run(&mut editor)
 }
 
@@ -0,0 +1,315 @@
+use std::{collections::HashMap, fmt, fs, path::Path};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use crate::editor::action::{Action, Direction, Location, Target};
+
+const HISTORY_LEN: usize = 100;
+
+/// What a prompt asks for. Each kind keeps its own history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PromptKind {
+    Command,
+    Path,
+    Search,
+    Confirm,
+}
+
+impl PromptKind {
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PromptKind::Command => ":",
+            PromptKind::Path => "Open: ",
+            PromptKind::Search => "/",
+            PromptKind::Confirm => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptEvent {
+    Pending,
+    Submit(String),
+    Cancel,
+}
+
+pub type Completer = Box<dyn Fn(&str) -> Vec<String>>;
+
+/// A one-line minibuffer, drawn in the message line. It takes the same actions as the
+/// main buffer, so it follows whatever keys are bound to them.
+pub struct Prompt {
+    kind: PromptKind,
+    label: String,
+    input: String,
+    cursor: usize,
+    history_idx: Option<usize>,
+    draft: String,
+    completer: Option<Completer>,
+    completions: Option<(Vec<String>, usize)>,
+}
+
+impl Prompt {
+
+    pub fn new(kind: PromptKind) -> Self {
+        Self {
+            kind,
+            label: kind.label().to_string(),
+            input: String::new(),
+            cursor: 0,
+            history_idx: None,
+            draft: String::new(),
+            completer: None,
+            completions: None,
+        }
+    }
+
+    /// A yes/no question, answered with a single `y` or `n`.
+    pub fn confirm(question: &str) -> Self {
+        Self::new(PromptKind::Confirm).with_label(&format!("{} (y/n) ", question))
+    }
+
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = label.to_string();
+        self
+    }
+
+    pub fn with_input(mut self, input: &str) -> Self {
+        self.input = input.to_string();
+        self.cursor = self.input.len();
+        self
+    }
+
+    /// Sets what Tab completes the input with.
+    pub fn with_completer<F: Fn(&str) -> Vec<String> + 'static>(mut self, completer: F) -> Self {
+        self.completer = Some(Box::new(completer));
+        self
+    }
+
+    pub fn kind(&self) -> PromptKind {
+        self.kind
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// Applies an action to the prompt. `history` holds earlier inputs of the same kind,
+    /// oldest first.
+    pub fn handle(&mut self, action: &Action, history: &[String]) -> PromptEvent {
+        use Direction::*;
+        if self.kind == PromptKind::Confirm {
+            return match action {
+                Action::Input(text) if text.eq_ignore_ascii_case("y") => PromptEvent::Submit("y".into()),
+                Action::Input(text) if text.eq_ignore_ascii_case("n") => PromptEvent::Cancel,
+                Action::ClearSelections | Action::Quit => PromptEvent::Cancel,
+                _ => PromptEvent::Pending,
+            };
+        }
+        if !matches!(action, Action::Input(t) if t == "\t") {
+            self.completions = None;
+        }
+        match action {
+            Action::Newline(_) => return PromptEvent::Submit(self.input.clone()),
+            Action::ClearSelections | Action::Quit => return PromptEvent::Cancel,
+            Action::Input(text) if text == "\t" => self.complete(),
+            Action::Input(text) => {
+                let text: String = text.chars().filter(|c| !c.is_control()).collect();
+                self.input.insert_str(self.cursor, &text);
+                self.cursor += text.len();
+            },
+            Action::Delete(Left(_)) if self.input.is_empty() => return PromptEvent::Cancel,
+            Action::Delete(Left(n)) => {
+                let start = self.back(*n as usize);
+                self.input.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            },
+            Action::Delete(Right(n)) => {
+                let end = self.forward(*n as usize);
+                self.input.replace_range(self.cursor..end, "");
+            },
+            Action::Delete(To(Location::Beginning(_))) => {
+                let start = self.word_start();
+                self.input.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            },
+            Action::Delete(To(Location::End(Target::Line))) => self.input.truncate(self.cursor),
+            Action::Delete(To(Location::End(_))) => {
+                let end = self.word_end();
+                self.input.replace_range(self.cursor..end, "");
+            },
+            Action::Move(Left(n)) => self.cursor = self.back(*n as usize),
+            Action::Move(Right(n)) => self.cursor = self.forward(*n as usize),
+            Action::Move(To(Location::Beginning(Target::Word))) => self.cursor = self.word_start(),
+            Action::Move(To(Location::End(Target::Word))) => self.cursor = self.word_end(),
+            Action::Move(To(Location::Beginning(_))) => self.cursor = 0,
+            Action::Move(To(Location::End(_))) => self.cursor = self.input.len(),
+            Action::Move(Up(_)) => self.recall(history, true),
+            Action::Move(Down(_)) => self.recall(history, false),
+            _ => {},
+        }
+        PromptEvent::Pending
+    }
+
+    /// The label and input as they fit in `width` columns, scrolled to keep the cursor
+    /// visible, and the column the cursor is drawn at.
+    pub fn render(&self, width: usize) -> (String, usize) {
+        let before = format!("{}{}", self.label, &self.input[..self.cursor]);
+        let mut skip = before.width().saturating_sub(width.saturating_sub(1));
+        let mut line = String::new();
+        let mut x = 0;
+        for g in format!("{}{}", self.label, self.input).graphemes(true) {
+            let w = g.width();
+            if skip > 0 {
+                skip = skip.saturating_sub(w);
+                continue;
+            }
+            if x + w > width {
+                break;
+            }
+            line.push_str(g);
+            x += w;
+        }
+        let cursor = before.width().min(width.saturating_sub(1));
+        (line, cursor)
+    }
+
+    fn back(&self, n: usize) -> usize {
+        self.input[..self.cursor].grapheme_indices(true).rev().take(n).last().map_or(self.cursor, |(i, _)| i)
+    }
+
+    fn forward(&self, n: usize) -> usize {
+        self.input[self.cursor..].grapheme_indices(true)
+            .nth(n)
+            .map_or(self.input.len(), |(i, _)| self.cursor + i)
+    }
+
+    /// Start of the word before the cursor, skipping blanks first.
+    fn word_start(&self) -> usize {
+        let chars: Vec<(usize, char)> = self.input[..self.cursor].char_indices().collect();
+        let mut i = chars.len();
+        while i > 0 && chars[i - 1].1.is_whitespace() {
+            i -= 1;
+        }
+        let word = i > 0 && is_word(chars[i - 1].1);
+        while i > 0 && !chars[i - 1].1.is_whitespace() && is_word(chars[i - 1].1) == word {
+            i -= 1;
+        }
+        chars.get(i).map_or(self.cursor, |(idx, _)| *idx)
+    }
+
+    /// End of the word after the cursor, skipping blanks first.
+    fn word_end(&self) -> usize {
+        let rest = &self.input[self.cursor..];
+        let mut chars = rest.char_indices().skip_while(|(_, c)| c.is_whitespace()).peekable();
+        let word = chars.peek().is_some_and(|(_, c)| is_word(*c));
+        chars.find(|(_, c)| c.is_whitespace() || is_word(*c) != word)
+            .map_or(self.input.len(), |(i, _)| self.cursor + i)
+    }
+
+    fn recall(&mut self, history: &[String], older: bool) {
+        let idx = match (self.history_idx, older) {
+            (None, true) if !history.is_empty() => {
+                self.draft = self.input.clone();
+                Some(history.len() - 1)
+            },
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i + 1 < history.len() => Some(i + 1),
+            (Some(_), false) => None,
+            (None, _) => return,
+        };
+        self.history_idx = idx;
+        self.input = idx.map_or_else(|| self.draft.clone(), |i| history[i].clone());
+        self.cursor = self.input.len();
+    }
+
+    /// Replaces the input with the next completion, cycling through them on every Tab.
+    fn complete(&mut self) {
+        let (candidates, idx) = match self.completions.take() {
+            Some((candidates, idx)) => {
+                let next = (idx + 1) % candidates.len();
+                (candidates, next)
+            },
+            None => match &self.completer {
+                Some(completer) => (completer(&self.input), 0),
+                None => return,
+            },
+        };
+        if let Some(candidate) = candidates.get(idx) {
+            self.input = candidate.clone();
+            self.cursor = self.input.len();
+            self.completions = Some((candidates, idx));
+        }
+    }
+}
+
+impl fmt::Debug for Prompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prompt")
+            .field("kind", &self.kind)
+            .field("label", &self.label)
+            .field("input", &self.input)
+            .field("cursor", &self.cursor)
+            .finish()
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Inputs submitted to each kind of prompt this session.
+#[derive(Debug, Default)]
+pub struct PromptHistory {
+    entries: HashMap<PromptKind, Vec<String>>,
+}
+
+impl PromptHistory {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, kind: PromptKind) -> &[String] {
+        self.entries.get(&kind).map_or(&[], |e| e.as_slice())
+    }
+
+    pub fn push(&mut self, kind: PromptKind, input: &str) {
+        if input.is_empty() || kind == PromptKind::Confirm {
+            return;
+        }
+        let entries = self.entries.entry(kind).or_default();
+        entries.retain(|e| e != input);
+        entries.push(input.to_string());
+        if entries.len() > HISTORY_LEN {
+            entries.remove(0);
+        }
+    }
+}
+
+/// Completes the last component of a path with the entries of its directory.
+pub fn complete_path(input: &str) -> Vec<String> {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(i) => (&input[..=i], &input[i + 1..]),
+        None => ("", input),
+    };
+    let read_from = if dir.is_empty() { Path::new(".") } else { Path::new(dir) };
+    let mut candidates: Vec<String> = fs::read_dir(read_from)
+        .map(|entries| entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                    return None;
+                }
+                let slash = if entry.path().is_dir() { "/" } else { "" };
+                Some(format!("{}{}{}", dir, name, slash))
+            })
+            .collect())
+        .unwrap_or_default();
+    candidates.sort();
+    candidates
+}
@@ -1,23 +1,29 @@
-use std::io::{self, stdout, stdin, Stdout, Read, Write};
+pub mod frame;
+pub mod backend;
+pub mod headless;
+
+use std::{collections::VecDeque, fmt, io::Write, sync::mpsc::Sender, time::{Duration, Instant}};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use crossterm::{
-    tty::IsTty,
-    cursor::{MoveTo, self},
-    write_ansi_code, execute, queue,
-    Command, Result as TermResult,
-    terminal::{self, LeaveAlternateScreen, EnterAlternateScreen, Clear, ClearType, ScrollUp, ScrollDown},
-    event::{Event, KeyEvent, read},
-    style::{Color, SetForegroundColor, SetBackgroundColor, SetColors},
+    event::{Event, KeyCode, KeyModifiers},
+    style::Color,
 };
+use backend::{Backend, Crossterm};
+use frame::Frame;
 
-use crate::{
-    editor::{Coords, Dir, action::Action},
+use crate::{editor::{Coords, Dir}, event::channel::{Events, Message}};
 
-};
+/// What terminal operations return.
+pub type TermResult<T> = std::io::Result<T>;
 
-#[derive(Debug)]
 pub struct Term {
     pub dims: Coords,
-    _stdout: io::Stdout,
+    backend: Box<dyn Backend>,
+    last: Frame,
+    /// Events that arrived while waiting for something else, to be read first.
+    deferred: VecDeque<Event>,
+    /// Whether input comes in on the editor's channel rather than from `read_event`.
+    listening: bool,
 }
 
 #[derive(Debug)]
@@ -33,94 +39,195 @@ pub enum TermOp {
     SetBg(Color),
     SetFg(Color),
     Scroll(Dir, u16),
+    CursorShape(CursorShape),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Block,
+    Bar,
+    Underline,
 }
 
 impl Term {
 
     pub fn new() -> TermResult<Self> {
-        let dims: Coords = terminal::size().unwrap_or_default().into();
-        terminal::enable_raw_mode()?;
-        execute!(io::stdout(), terminal::SetTitle("dd"))?;
-        Ok ( Self { dims, _stdout: io::stdout() })
+        Ok(Self::with_backend(Crossterm::new()?))
+    }
+
+    pub fn with_backend<B: Backend + 'static>(backend: B) -> Self {
+        Self {
+            dims: backend.size(), backend: Box::new(backend), last: Frame::default(),
+            deferred: VecDeque::new(), listening: false,
+        }
+    }
+
+    /// A terminal of the given size whose output goes to `out` instead of stdout.
+    pub fn with_output<W: Write + 'static>(dims: Coords, out: W) -> Self {
+        Self::with_backend(Crossterm::with_output(dims, out))
+    }
+
+    pub fn init(&mut self) -> TermResult<()> {
+        self.ex(TermOp::Enter)?;
+        Ok(())
+    }
+
+    pub fn ex(&mut self, operation: TermOp) -> TermResult<()> {
+        self.backend.execute(operation)
     }
 
-    pub fn init(&self) -> TermResult<()> {
-        Self::ex(TermOp::Enter)?;
+    /// Draws `frame`, leaving it to the backend to update only what changed since the
+    /// last one drawn.
+    pub fn draw(&mut self, frame: &Frame) -> TermResult<()> {
+        self.backend.draw(frame, &self.last)?;
+        self.last = frame.clone();
         Ok(())
     }
 
+    /// Switches to a new size, and repaints the whole screen on the next `draw`.
+    pub fn resize(&mut self, dims: Coords) {
+        self.backend.resize(dims.clone());
+        self.dims = dims;
+        self.invalidate();
+    }
 
-    pub fn ex(operation: TermOp) -> TermResult<()> {
-        let mut so: io::Stdout = io::stdout();
-        use TermOp::*;
-        match operation {
-            Enter => {
-                terminal::enable_raw_mode()?;
-                crossterm::queue!(so, terminal::Clear(ClearType::All))?;
-                execute!(so, EnterAlternateScreen)?
-            },
-            Clear => execute!(so, terminal::Clear(ClearType::All))?,
-            ClearLn => execute!(so, terminal::Clear(ClearType::CurrentLine))?,
-            Exit => {
-                execute!(so, LeaveAlternateScreen)?;
-                terminal::disable_raw_mode()?;
-            }
-            CursorEnabled(true) => execute!(so, cursor::Show)?,
-            CursorEnabled(false) => execute!(so, cursor::Hide)?,
-            Flush => so.flush()?,
-            SetCursor(c) => execute!(so, MoveTo(c.x as u16, c.y as u16))?,
-            SetBg(color) => execute!(so, SetBackgroundColor(color))?,
-            SetFg(color) => execute!(so, SetForegroundColor(color))?,
-            Move(dir, amt) => match dir {
-                Dir::Up => execute!(so, cursor::MoveToPreviousLine(amt))?,
-                Dir::Down => execute!(so, cursor::MoveToNextLine(amt))?,
-                Dir::Left => execute!(so, cursor::MoveLeft(amt))?,
-                Dir::Right => execute!(so, cursor::MoveRight(amt))?,
-                _ => (),
-            },
-            Scroll(dir, amt) => match dir {
-                Dir::Up => execute!(so, ScrollUp(amt))?,
-                Dir::Down => execute!(so, ScrollDown(amt))?,
-                _ => (),
-            },
+    /// Makes the next `draw` repaint the whole screen.
+    pub fn invalidate(&mut self) {
+        self.last = Frame::default();
+    }
+
+    /// The next input event, or `None` once there is no more input.
+    pub fn read_event(&mut self) -> TermResult<Option<Event>> {
+        match self.deferred.pop_front() {
+            Some(event) => Ok(Some(event)),
+            None => self.backend.read(),
         }
-        Ok(())
     }
 
+    pub fn poll_event(&mut self, timeout: Duration) -> TermResult<bool> {
+        if !self.deferred.is_empty() {
+            return Ok(true);
+        }
+        self.backend.poll(timeout)
+    }
+
+    /// Sends input to `sender` from now on, if the backend can read it in the background.
+    pub fn listen(&mut self, sender: Sender<Message>) -> bool {
+        if !self.listening {
+            self.listening = self.backend.listen(sender);
+        }
+        self.listening
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.listening
+    }
+
+    /// The next input event before `deadline`, from `events` when input comes in on
+    /// the editor's channel.
+    fn next_event(&mut self, events: &mut Events, deadline: Instant) -> TermResult<Option<Event>> {
+        if self.listening {
+            return Ok(events.next_input(deadline));
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left == Duration::from_millis(0) || !self.backend.poll(left)? {
+            return Ok(None);
+        }
+        self.backend.read()
+    }
+
+    /// Puts back an event read out of turn, to be handed out again by whichever of
+    /// `events` and `read_event` input comes from.
+    fn defer(&mut self, events: &mut Events, event: Event) {
+        if self.listening {
+            events.defer(Message::Input(event));
+        } else {
+            self.deferred.push_back(event);
+        }
+    }
 
-    pub fn size(self) -> Coords {
-        self.dims
+    /// Sets the system clipboard with an OSC 52 escape sequence, which the terminal
+    /// forwards to its host, so it works over SSH as well.
+    pub fn set_clipboard(&mut self, text: &str) -> TermResult<()> {
+        let sequence = format!("\x1b]52;c;{}\x07", BASE64.encode(text));
+        self.backend.write(passthrough(&sequence).as_bytes())
     }
 
-    pub fn read_key() -> TermResult<Action> {
+    /// Asks the terminal for the system clipboard with OSC 52, waiting up to `timeout` for
+    /// its reply. Most terminals don't answer, or only when configured to.
+    pub fn get_clipboard(&mut self, events: &mut Events, timeout: Duration) -> TermResult<Option<String>> {
+        self.backend.write(passthrough("\x1b]52;c;?\x07").as_bytes())?;
+        let deadline = Instant::now() + timeout;
+        // Only once the reply has started.
+        let mut reply: Option<String> = None;
         loop {
-            if let Event::Key(key_event) = read()? {
-                let act = Action::from((key_event.code, key_event.modifiers));
-                return Ok(act);
+            // The reply is read as keys: ESC ] arrives as Alt+], and it ends with BEL
+            // (Ctrl+G) or ST (Alt+\). Anything else, like keys typed meanwhile, is read
+            // again afterwards.
+            let event = match self.next_event(events, deadline)? {
+                Some(event) => event,
+                None => return Ok(None),
+            };
+            let key = match event {
+                Event::Key(key) => key,
+                _ => {
+                    self.defer(events, event);
+                    continue;
+                },
+            };
+            match (key.code, key.modifiers, reply.as_mut()) {
+                (KeyCode::Char(']'), KeyModifiers::ALT, _) => reply = Some(String::new()),
+                (KeyCode::Char('g'), KeyModifiers::CONTROL, Some(body))
+                    | (KeyCode::Char('\\'), KeyModifiers::ALT, Some(body)) => return Ok(parse_osc52(body)),
+                (KeyCode::Char(c), _, Some(body)) => body.push(c),
+                _ => self.defer(events, event),
             }
         }
     }
 
+    pub fn size(&self) -> Coords {
+        self.dims.clone()
+    }
+
     pub fn colors() -> usize {
-        if std::env::var("COLORTERM").unwrap_or_else(|_| "".into()).eq("truecolor".into()) {
-            return 24;
+        if std::env::var("COLORTERM").unwrap_or_else(|_| "".into()).eq("truecolor") {
+            24
         } else if let Ok(inf) = term::terminfo::TermInfo::from_env() {
-            if let Some(num) = inf.numbers.get("colors") {
-                match num {
-                    &256 => return 256,
-                    _ => return 16,
-                }
-            } else { return 16 }
-        } else { return 16  }
+            match inf.numbers.get("colors") {
+                Some(&256) => 256,
+                _ => 16,
+            }
+        } else { 16  }
     }
 
 }
 
 impl Default for Term {
     fn default() -> Self {
-        let dims: Coords = terminal::size().unwrap_or_default().into();
-        Self {
-            dims, _stdout: stdout(),
-        }
+        Self::with_backend(Crossterm::default())
+    }
+}
+
+impl fmt::Debug for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Term").field("dims", &self.dims).finish()
+    }
+}
+
+/// Wraps an escape sequence so tmux or screen pass it on to the terminal they run in
+/// instead of taking it themselves. Escapes inside are doubled for tmux.
+fn passthrough(sequence: &str) -> String {
+    if std::env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else if std::env::var_os("STY").is_some() {
+        format!("\x1bP{}\x1b\\", sequence)
+    } else {
+        sequence.to_string()
     }
 }
+
+/// Decodes the body of an OSC 52 reply, `52;c;<base64>`.
+fn parse_osc52(reply: &str) -> Option<String> {
+    let data = reply.strip_prefix("52;")?.split(';').nth(1)?;
+    String::from_utf8(BASE64.decode(data).ok()?).ok()
+}
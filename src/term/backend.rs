@@ -0,0 +1,239 @@
+use std::{
+    io::{self, Write}, pin::Pin, sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::Sender},
+    task::{Context, Poll, Wake, Waker}, thread::{self, Thread}, time::Duration,
+};
+use futures_core::Stream;
+use crossterm::{
+    cursor::{self, MoveTo}, execute, queue,
+    terminal::{self, LeaveAlternateScreen, EnterAlternateScreen, ClearType, ScrollUp, ScrollDown},
+    event::{self, Event},
+    style::{
+        Attribute, Colors, Print, SetAttribute, SetAttributes, SetColors,
+        SetForegroundColor, SetBackgroundColor,
+    },
+};
+use crate::{editor::{Coords, Dir}, event::channel::Message, term::{CursorShape as Shape, TermOp, TermResult, frame::{Frame, Style}}};
+
+/// Whether the real terminal was put in raw mode and on the alternate screen, and not
+/// yet taken out again.
+static ENTERED: AtomicBool = AtomicBool::new(false);
+
+/// Takes the terminal out of raw mode and the alternate screen if the editor left it
+/// there, e.g. from a panic hook. Does nothing once the terminal was restored.
+pub fn restore() {
+    if ENTERED.swap(false, Ordering::SeqCst) {
+        let mut out = io::stdout();
+        let _ = out.write_all(b"\x1b[0 q");
+        let _ = execute!(out,
+            event::DisableBracketedPaste, event::DisableMouseCapture, LeaveAlternateScreen, cursor::Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Where `Term` sends its output and gets its input from.
+pub trait Backend {
+
+    fn size(&self) -> Coords;
+
+    /// Takes note of the terminal's new size after it was resized.
+    fn resize(&mut self, dims: Coords);
+
+    fn execute(&mut self, op: TermOp) -> TermResult<()>;
+
+    /// Brings the screen from `prev` to `frame`.
+    fn draw(&mut self, frame: &Frame, prev: &Frame) -> TermResult<()>;
+
+    /// Writes raw bytes, such as escape sequences the backend has no operation for.
+    fn write(&mut self, bytes: &[u8]) -> TermResult<()>;
+
+    /// Whether an event can be read within `timeout`.
+    fn poll(&mut self, timeout: Duration) -> TermResult<bool>;
+
+    /// The next input event, or `None` once there is no more input.
+    fn read(&mut self) -> TermResult<Option<Event>>;
+
+    /// Starts sending input events to `sender` from a thread of its own, returning
+    /// whether it did. Backends that don't are read with `poll` and `read` instead.
+    fn listen(&mut self, _sender: Sender<Message>) -> bool {
+        false
+    }
+}
+
+/// A real terminal, driven through crossterm.
+pub struct Crossterm {
+    dims: Coords,
+    out: Box<dyn Write>,
+}
+
+impl Crossterm {
+
+    pub fn new() -> TermResult<Self> {
+        terminal::enable_raw_mode()?;
+        ENTERED.store(true, Ordering::SeqCst);
+        execute!(io::stdout(), terminal::SetTitle("dd"))?;
+        Ok(Self::default())
+    }
+
+    /// A terminal of the given size whose output goes to `out` instead of stdout.
+    pub fn with_output<W: Write + 'static>(dims: Coords, out: W) -> Self {
+        Self { dims, out: Box::new(out) }
+    }
+}
+
+impl Default for Crossterm {
+    fn default() -> Self {
+        let dims: Coords = terminal::size().unwrap_or_default().into();
+        Self { dims, out: Box::new(io::stdout()) }
+    }
+}
+
+impl Backend for Crossterm {
+
+    fn size(&self) -> Coords {
+        self.dims.clone()
+    }
+
+    fn resize(&mut self, dims: Coords) {
+        self.dims = dims;
+    }
+
+    fn execute(&mut self, operation: TermOp) -> TermResult<()> {
+        let so = &mut self.out;
+        use TermOp::*;
+        match operation {
+            Enter => {
+                terminal::enable_raw_mode()?;
+                ENTERED.store(true, Ordering::SeqCst);
+                crossterm::queue!(so, terminal::Clear(ClearType::All))?;
+                execute!(so, EnterAlternateScreen, event::EnableMouseCapture, event::EnableBracketedPaste)?
+            },
+            Clear => execute!(so, terminal::Clear(ClearType::All))?,
+            ClearLn => execute!(so, terminal::Clear(ClearType::CurrentLine))?,
+            Exit => {
+                ENTERED.store(false, Ordering::SeqCst);
+                // Back to the terminal's default cursor.
+                so.write_all(b"\x1b[0 q")?;
+                execute!(so,
+                    event::DisableBracketedPaste, event::DisableMouseCapture, LeaveAlternateScreen, cursor::Show)?;
+                terminal::disable_raw_mode()?;
+            }
+            CursorEnabled(true) => execute!(so, cursor::Show)?,
+            CursorEnabled(false) => execute!(so, cursor::Hide)?,
+            Flush => so.flush()?,
+            SetCursor(c) => execute!(so, MoveTo(c.x as u16, c.y as u16))?,
+            SetBg(color) => execute!(so, SetBackgroundColor(color))?,
+            SetFg(color) => execute!(so, SetForegroundColor(color))?,
+            Move(dir, amt) => match dir {
+                Dir::Up => execute!(so, cursor::MoveToPreviousLine(amt))?,
+                Dir::Down => execute!(so, cursor::MoveToNextLine(amt))?,
+                Dir::Left => execute!(so, cursor::MoveLeft(amt))?,
+                Dir::Right => execute!(so, cursor::MoveRight(amt))?,
+                _ => (),
+            },
+            Scroll(dir, amt) => match dir {
+                Dir::Up => execute!(so, ScrollUp(amt))?,
+                Dir::Down => execute!(so, ScrollDown(amt))?,
+                _ => (),
+            },
+            // DECSCUSR, steady block, underline or bar.
+            CursorShape(shape) => {
+                let n = match shape {
+                    Shape::Block => 2,
+                    Shape::Underline => 4,
+                    Shape::Bar => 6,
+                };
+                write!(so, "\x1b[{} q", n)?;
+                so.flush()?;
+            },
+        }
+        Ok(())
+    }
+
+    /// Writes only the cells that changed and flushes once at the end.
+    fn draw(&mut self, frame: &Frame, prev: &Frame) -> TermResult<()> {
+        let out = &mut self.out;
+        queue!(out, cursor::Hide)?;
+        if frame.dims() != prev.dims() {
+            queue!(out, SetAttribute(Attribute::Reset), terminal::Clear(ClearType::All))?;
+        }
+        let mut style: Option<Style> = None;
+        let mut at: Option<Coords> = None;
+        for (pos, cell) in frame.diff(prev) {
+            if at.as_ref() != Some(&pos) {
+                queue!(out, MoveTo(pos.x as u16, pos.y as u16))?;
+            }
+            if style != Some(cell.style) {
+                queue!(out,
+                    SetAttribute(Attribute::Reset),
+                    SetColors(Colors::new(cell.style.fg, cell.style.bg)),
+                    SetAttributes(cell.style.attrs))?;
+                style = Some(cell.style);
+            }
+            queue!(out, Print(&cell.symbol))?;
+            at = Some(Coords { x: pos.x + cell.width, y: pos.y });
+        }
+        if style.is_some() {
+            queue!(out, SetAttribute(Attribute::Reset))?;
+        }
+        if let Some(c) = &frame.cursor {
+            queue!(out, MoveTo(c.x as u16, c.y as u16), cursor::Show)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> TermResult<()> {
+        self.out.write_all(bytes)?;
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn poll(&mut self, timeout: Duration) -> TermResult<bool> {
+        event::poll(timeout)
+    }
+
+    fn read(&mut self) -> TermResult<Option<Event>> {
+        event::read().map(Some)
+    }
+
+    /// Reads crossterm's event stream, waking the thread when the stream has more.
+    fn listen(&mut self, sender: Sender<Message>) -> bool {
+        thread::spawn(move || {
+            let mut events = event::EventStream::new();
+            let waker = Waker::from(Arc::new(Unpark(thread::current())));
+            let mut cx = Context::from_waker(&waker);
+            loop {
+                let message = match Pin::new(&mut events).poll_next(&mut cx) {
+                    Poll::Ready(Some(Ok(event))) => Message::Input(event),
+                    Poll::Ready(Some(Err(_))) | Poll::Ready(None) => Message::Closed,
+                    Poll::Pending => {
+                        thread::park();
+                        continue;
+                    },
+                };
+                let closed = matches!(message, Message::Closed);
+                if sender.send(message).is_err() || closed {
+                    return;
+                }
+            }
+        });
+        true
+    }
+}
+
+/// Restores the terminal on every way out, including errors and unwinding.
+impl Drop for Crossterm {
+    fn drop(&mut self) {
+        if ENTERED.load(Ordering::SeqCst) {
+            let _ = self.execute(TermOp::Exit);
+        }
+    }
+}
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
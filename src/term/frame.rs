@@ -0,0 +1,162 @@
+use crossterm::style::{Attribute, Attributes, Color};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use crate::editor::Coords;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Color,
+    pub bg: Color,
+    pub attrs: Attributes,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self { fg: Color::Reset, bg: Color::Reset, attrs: Attributes::default() }
+    }
+}
+
+impl Style {
+
+    pub fn fg(mut self, color: Color) -> Self {
+        self.fg = color;
+        self
+    }
+
+    pub fn bg(mut self, color: Color) -> Self {
+        self.bg = color;
+        self
+    }
+
+    pub fn attr(mut self, attr: Attribute) -> Self {
+        self.attrs.set(attr);
+        self
+    }
+}
+
+/// One terminal cell. A grapheme wider than one column is stored in its first cell,
+/// followed by empty cells of width zero that it covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: String,
+    pub width: usize,
+    pub style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self { symbol: " ".into(), width: 1, style: Style::default() }
+    }
+}
+
+impl Cell {
+
+    fn blank(style: Style) -> Self {
+        Self { style, ..Self::default() }
+    }
+
+    fn continuation(style: Style) -> Self {
+        Self { symbol: String::new(), width: 0, style }
+    }
+}
+
+/// A grid of cells the editor draws a whole screen into, so only the cells that changed
+/// since the last frame have to be written to the terminal.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Frame {
+    dims: Coords,
+    cells: Vec<Cell>,
+    pub cursor: Option<Coords>,
+}
+
+impl Frame {
+
+    pub fn new(dims: Coords) -> Self {
+        let cells = vec![Cell::default(); dims.x * dims.y];
+        Self { dims, cells, cursor: None }
+    }
+
+    pub fn dims(&self) -> &Coords {
+        &self.dims
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&Cell> {
+        if x < self.dims.x && y < self.dims.y {
+            self.cells.get(y * self.dims.x + x)
+        } else {
+            None
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, cell: Cell) {
+        let idx = y * self.dims.x + x;
+        // Overwriting half of a wide grapheme leaves the other half blank.
+        if self.cells[idx].width == 0 && x > 0 {
+            let style = self.cells[idx - 1].style;
+            if self.cells[idx - 1].width > 1 {
+                self.cells[idx - 1] = Cell::blank(style);
+            }
+        }
+        if self.cells[idx].width > 1 && x + 1 < self.dims.x && cell.width < 2 {
+            let style = self.cells[idx + 1].style;
+            self.cells[idx + 1] = Cell::blank(style);
+        }
+        self.cells[idx] = cell;
+    }
+
+    /// Writes `text` on line `y` from column `x`, cutting it at the right edge, and
+    /// returns the column just after it.
+    pub fn print(&mut self, mut x: usize, y: usize, text: &str, style: Style) -> usize {
+        if y >= self.dims.y {
+            return x;
+        }
+        for grapheme in text.graphemes(true) {
+            let width = grapheme.width();
+            if width == 0 {
+                continue;
+            }
+            if x + width > self.dims.x {
+                while x < self.dims.x {
+                    self.set(x, y, Cell::blank(style));
+                    x += 1;
+                }
+                break;
+            }
+            self.set(x, y, Cell { symbol: grapheme.to_string(), width, style });
+            for i in 1..width {
+                self.set(x + i, y, Cell::continuation(style));
+            }
+            x += width;
+        }
+        x
+    }
+
+    /// Blanks line `y` from column `x` to the right edge with `style`.
+    pub fn fill(&mut self, x: usize, y: usize, style: Style) {
+        if y < self.dims.y {
+            for x in x..self.dims.x {
+                self.set(x, y, Cell::blank(style));
+            }
+        }
+    }
+
+    /// The text on line `y`, without styles.
+    pub fn line(&self, y: usize) -> String {
+        if y >= self.dims.y {
+            return String::new();
+        }
+        let start = y * self.dims.x;
+        self.cells[start..start + self.dims.x].iter().map(|c| c.symbol.as_str()).collect()
+    }
+
+    /// Cells that differ from `prev`, in drawing order. Every cell differs if the
+    /// frames have different sizes.
+    pub fn diff<'a>(&'a self, prev: &Frame) -> Vec<(Coords, &'a Cell)> {
+        let full = self.dims != prev.dims;
+        self.cells.iter()
+            .enumerate()
+            .filter(|(i, cell)| cell.width > 0 && (full || prev.cells[*i] != **cell))
+            .map(|(i, cell)| (Coords { x: i % self.dims.x, y: i / self.dims.x }, cell))
+            .collect()
+    }
+}
@@ -0,0 +1,149 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc, thread, time::Duration};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crate::{editor::Coords, term::{CursorShape, TermOp, TermResult, backend::Backend, frame::Frame}};
+
+#[derive(Debug, Default)]
+struct State {
+    dims: Coords,
+    /// Scripted input, where `None` is a pause.
+    events: VecDeque<Option<Event>>,
+    screen: Frame,
+    output: Vec<u8>,
+    entered: bool,
+    cursor_shape: Option<CursorShape>,
+}
+
+/// An in-memory terminal for tests. Events are scripted up front and read in order,
+/// and whatever was drawn last can be read back as text. Clones share the same
+/// terminal, so one can be handed to `Term` while the test keeps another.
+#[derive(Debug, Clone, Default)]
+pub struct Headless {
+    state: Rc<RefCell<State>>,
+}
+
+impl Headless {
+
+    pub fn new(dims: Coords) -> Self {
+        let state = State { screen: Frame::new(dims.clone()), dims, ..Default::default() };
+        Self { state: Rc::new(RefCell::new(state)) }
+    }
+
+    pub fn push_event(&self, event: Event) {
+        self.state.borrow_mut().events.push_back(Some(event));
+    }
+
+    /// Queues a pause, as when the user stops typing: the next poll waits out its
+    /// timeout and reports nothing to read.
+    pub fn pause(&self) {
+        self.state.borrow_mut().events.push_back(None);
+    }
+
+    pub fn push_key(&self, code: KeyCode, modifiers: KeyModifiers) {
+        self.push_event(Event::Key(KeyEvent::new(code, modifiers)));
+    }
+
+    /// Queues a key press for every char of `text`, with Enter for newlines.
+    pub fn type_str(&self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\n' => self.push_key(KeyCode::Enter, KeyModifiers::NONE),
+                '\t' => self.push_key(KeyCode::Tab, KeyModifiers::NONE),
+                c if c.is_uppercase() => self.push_key(KeyCode::Char(c), KeyModifiers::SHIFT),
+                c => self.push_key(KeyCode::Char(c), KeyModifiers::NONE),
+            }
+        }
+    }
+
+    /// Queues text pasted into the terminal, as one event.
+    pub fn paste(&self, text: &str) {
+        self.push_event(Event::Paste(text.to_string()));
+    }
+
+    /// The screen as drawn last, one line per row with trailing blanks trimmed.
+    pub fn screen(&self) -> String {
+        let state = self.state.borrow();
+        (0..state.screen.dims().y)
+            .map(|y| state.screen.line(y).trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn line(&self, y: usize) -> String {
+        self.state.borrow().screen.line(y)
+    }
+
+    pub fn frame(&self) -> Frame {
+        self.state.borrow().screen.clone()
+    }
+
+    pub fn cursor(&self) -> Option<Coords> {
+        self.state.borrow().screen.cursor.clone()
+    }
+
+    /// Raw bytes written outside of drawing, such as OSC 52 sequences.
+    pub fn output(&self) -> Vec<u8> {
+        self.state.borrow().output.clone()
+    }
+
+    /// Whether the editor has entered and not yet left the screen.
+    pub fn is_entered(&self) -> bool {
+        self.state.borrow().entered
+    }
+
+    pub fn cursor_shape(&self) -> Option<CursorShape> {
+        self.state.borrow().cursor_shape
+    }
+}
+
+impl Backend for Headless {
+
+    fn size(&self) -> Coords {
+        self.state.borrow().dims.clone()
+    }
+
+    fn resize(&mut self, dims: Coords) {
+        self.state.borrow_mut().dims = dims;
+    }
+
+    fn execute(&mut self, op: TermOp) -> TermResult<()> {
+        let mut state = self.state.borrow_mut();
+        match op {
+            TermOp::Enter => state.entered = true,
+            TermOp::Exit => state.entered = false,
+            TermOp::Clear => state.screen = Frame::new(state.dims.clone()),
+            TermOp::CursorShape(shape) => state.cursor_shape = Some(shape),
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &Frame, _prev: &Frame) -> TermResult<()> {
+        self.state.borrow_mut().screen = frame.clone();
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> TermResult<()> {
+        self.state.borrow_mut().output.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Reading never blocks: it returns the next scripted event or the end of input.
+    /// Only a pause makes a poll wait.
+    fn poll(&mut self, timeout: Duration) -> TermResult<bool> {
+        let mut state = self.state.borrow_mut();
+        if let Some(None) = state.events.front() {
+            state.events.pop_front();
+            thread::sleep(timeout);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn read(&mut self) -> TermResult<Option<Event>> {
+        let mut state = self.state.borrow_mut();
+        while let Some(None) = state.events.front() {
+            state.events.pop_front();
+        }
+        Ok(state.events.pop_front().flatten())
+    }
+}
@@ -0,0 +1,30 @@
+use ded::buffer::Buffer;
+
+#[test]
+fn find_matches_across_chunks() {
+    // Long enough for the rope to split it into several chunks.
+    let text = format!("{}needle{}é needle", "a".repeat(5000), "b".repeat(5000));
+    let buffer = Buffer::from(text.as_str());
+    assert!(buffer.chunks().count() > 1);
+    for at in (0..5000).step_by(7) {
+        assert_eq!(buffer.find("needle", at), Some(5000));
+    }
+    assert_eq!(buffer.find("needle", 5001), Some(10008));
+}
+
+#[test]
+fn find_wraps_around() {
+    let buffer = Buffer::from("one two one");
+    assert_eq!(buffer.find("one", 1), Some(8));
+    assert_eq!(buffer.find("one", 9), Some(0));
+    assert_eq!(buffer.find("two", 4), Some(4));
+    assert_eq!(buffer.find("three", 0), None);
+    assert_eq!(buffer.find("", 3), Some(3));
+}
+
+#[test]
+fn find_handles_repeated_prefixes() {
+    let buffer = Buffer::from("aaab aabaab");
+    assert_eq!(buffer.find("aab", 0), Some(1));
+    assert_eq!(buffer.find("aabaab", 0), Some(5));
+}
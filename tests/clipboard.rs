@@ -0,0 +1,152 @@
+mod common;
+
+use std::{env, sync::{Mutex, MutexGuard}, time::Duration};
+use crossterm::event::{Event, KeyCode, KeyModifiers};
+use ded::{
+    editor::{Coords, clipboard::{Clipboard, Provider}},
+    event::channel::Events,
+    term::{Term, headless::Headless},
+};
+use common::{captured, config, ctrl, editor_with, run, temp_file};
+
+/// Tests that read or set the environment take turns, so one doesn't leak into another.
+static ENV: Mutex<()> = Mutex::new(());
+
+fn outside_multiplexers() -> MutexGuard<'static, ()> {
+    let guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    env::remove_var("TMUX");
+    env::remove_var("STY");
+    guard
+}
+
+fn headless() -> (Term, Headless) {
+    let headless = Headless::new(Coords { x: 20, y: 5 });
+    (Term::with_backend(headless.clone()), headless)
+}
+
+/// Queues an OSC 52 reply the way it arrives: as keys, from Alt+] to Ctrl+G.
+fn reply(term: &Headless, body: &str) {
+    term.push_key(KeyCode::Char(']'), KeyModifiers::ALT);
+    term.type_str(body);
+    term.push_key(KeyCode::Char('g'), KeyModifiers::CONTROL);
+}
+
+#[test]
+fn copying_writes_osc52() {
+    let _env = outside_multiplexers();
+    let (mut term, sink) = captured(20, 5);
+    term.set_clipboard("hello").unwrap();
+    assert_eq!(sink.take(), b"\x1b]52;c;aGVsbG8=\x07");
+}
+
+#[test]
+fn copying_inside_tmux_or_screen_passes_osc52_through() {
+    let _env = outside_multiplexers();
+    env::set_var("TMUX", "/tmp/tmux-0/default,1,0");
+    let (mut term, sink) = captured(20, 5);
+    term.set_clipboard("hello").unwrap();
+    env::remove_var("TMUX");
+    assert_eq!(sink.take(), b"\x1bPtmux;\x1b\x1b]52;c;aGVsbG8=\x07\x1b\\");
+
+    env::set_var("STY", "1234.pts-0.host");
+    let (mut term, sink) = captured(20, 5);
+    term.set_clipboard("hello").unwrap();
+    env::remove_var("STY");
+    assert_eq!(sink.take(), b"\x1bP\x1b]52;c;aGVsbG8=\x07\x1b\\");
+}
+
+#[test]
+fn the_clipboard_writes_through_its_provider() {
+    let _env = outside_multiplexers();
+    let (mut term, sink) = captured(20, 5);
+    let mut clipboard = Clipboard::new(Provider::Internal);
+    clipboard.copy(&mut term, "kept").unwrap();
+    assert!(sink.take().is_empty());
+    assert_eq!(clipboard.paste(&mut term, &mut Events::new()).unwrap().as_deref(), Some("kept"));
+
+    clipboard.set_provider(Provider::Osc52);
+    clipboard.copy(&mut term, "sent").unwrap();
+    assert_eq!(sink.take(), b"\x1b]52;c;c2VudA==\x07");
+}
+
+#[test]
+fn providers_parse_from_names_and_commands() {
+    assert_eq!("osc52".parse(), Ok(Provider::Osc52));
+    assert_eq!("none".parse(), Ok(Provider::Internal));
+    assert_eq!("tmux".parse(), Ok(Provider::command("tmux load-buffer -", "tmux save-buffer -")));
+    assert_eq!("my-copy --in | my-paste".parse(), Ok(Provider::command("my-copy --in", "my-paste")));
+    assert!(" | paste".parse::<Provider>().is_err());
+}
+
+#[test]
+#[cfg(not(target_os = "macos"))]
+fn tmux_without_a_display_uses_osc52() {
+    let _env = outside_multiplexers();
+    let saved: Vec<_> = ["SSH_TTY", "SSH_CONNECTION", "WAYLAND_DISPLAY", "DISPLAY"].iter()
+        .map(|name| (name, env::var_os(name)))
+        .collect();
+    for (name, _) in &saved {
+        env::remove_var(name);
+    }
+    env::set_var("TMUX", "/tmp/tmux-0/default,1,0");
+    let provider = Provider::detect();
+    env::remove_var("TMUX");
+    for (name, value) in saved {
+        if let Some(value) = value {
+            env::set_var(name, value);
+        }
+    }
+    assert_eq!(provider, Provider::Osc52);
+}
+
+#[test]
+fn pasting_reads_the_reply() {
+    let _env = outside_multiplexers();
+    let (mut term, headless) = headless();
+    reply(&headless, "52;c;aGk=");
+    assert_eq!(term.get_clipboard(&mut Events::new(), Duration::from_millis(100)).unwrap().as_deref(), Some("hi"));
+    assert_eq!(headless.output(), b"\x1b]52;c;?\x07");
+}
+
+#[test]
+fn input_around_the_reply_is_read_afterwards() {
+    let _env = outside_multiplexers();
+    let (mut term, headless) = headless();
+    headless.type_str("x");
+    headless.push_event(Event::Resize(30, 6));
+    reply(&headless, "52;c;aGk=");
+    headless.type_str("y");
+    assert_eq!(term.get_clipboard(&mut Events::new(), Duration::from_millis(100)).unwrap().as_deref(), Some("hi"));
+    match term.read_event().unwrap() {
+        Some(Event::Key(key)) => assert_eq!(key.code, KeyCode::Char('x')),
+        other => panic!("expected the typed key, got {:?}", other),
+    }
+    assert!(matches!(term.read_event().unwrap(), Some(Event::Resize(30, 6))));
+    match term.read_event().unwrap() {
+        Some(Event::Key(key)) => assert_eq!(key.code, KeyCode::Char('y')),
+        other => panic!("expected the typed key, got {:?}", other),
+    }
+    assert!(term.read_event().unwrap().is_none());
+}
+
+#[test]
+fn no_reply_pastes_nothing() {
+    let _env = outside_multiplexers();
+    let (mut term, _headless) = headless();
+    assert_eq!(term.get_clipboard(&mut Events::new(), Duration::from_millis(10)).unwrap(), None);
+}
+
+#[test]
+fn copying_with_osc52_sets_the_system_clipboard() {
+    let _env = outside_multiplexers();
+    let mut config = config();
+    config.clipboard = Provider::Osc52;
+    config.clipboard_sync = true;
+    let path = temp_file("osc52-copy", "hello\nworld\n");
+    let (mut editor, term) = editor_with(config, &[path], 40, 6);
+    term.push_key(KeyCode::Down, KeyModifiers::SHIFT);
+    ctrl(&term, 'c');
+    run(&mut editor);
+    let output = String::from_utf8(term.output()).unwrap();
+    assert!(output.contains("\x1b]52;c;aGVsbG8K\x07"), "{:?}", output);
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use crossterm::event::KeyCode;
+use ded::editor::{Coords, command::{Arg, Registry}};
+use common::{ctrl, editor, key, rows, run, temp_file};
+
+/// The 0-based line range and command name `line` parses to, with the cursor on line 3
+/// of 10.
+fn parse(line: &str) -> Result<(Option<std::ops::Range<usize>>, &'static str), String> {
+    Registry::new().parse(line, 2, 9).map(|(command, inv)| (inv.range, command.name))
+}
+
+#[test]
+fn ranges_resolve_against_the_cursor_and_the_last_line() {
+    assert_eq!(parse("d"), Ok((None, "delete")));
+    assert_eq!(parse("%d"), Ok((Some(0..10), "delete")));
+    assert_eq!(parse("2,4d"), Ok((Some(1..4), "delete")));
+    assert_eq!(parse("4,2d"), Ok((Some(1..4), "delete")));
+    assert_eq!(parse(".,$y"), Ok((Some(2..10), "yank")));
+    assert_eq!(parse(".+2y"), Ok((Some(4..5), "yank")));
+    assert_eq!(parse("$-1,$--y"), Ok((Some(7..9), "yank")));
+    assert_eq!(parse("-5d"), Ok((Some(0..1), "delete")));
+    assert_eq!(parse(":20d"), Ok((Some(9..10), "delete")));
+}
+
+#[test]
+fn huge_offsets_stop_at_the_last_line() {
+    assert_eq!(parse(&format!(".+{}d", usize::MAX)), Ok((Some(9..10), "delete")));
+    assert_eq!(parse(&format!("$+{},1d", usize::MAX)), Ok((Some(0..10), "delete")));
+    assert!(parse("99999999999999999999999d").is_err());
+}
+
+#[test]
+fn a_range_alone_goes_to_its_line() {
+    let (command, inv) = Registry::new().parse("7", 2, 9).unwrap();
+    assert_eq!(command.name, "goto");
+    assert_eq!(inv.args, [Arg::Number(7)]);
+}
+
+#[test]
+fn bad_command_lines_are_explained() {
+    assert_eq!(parse("2,4q"), Err("quit doesn't take a range".into()));
+    assert_eq!(parse("frob"), Err("Not an editor command: frob".into()));
+    assert_eq!(parse("1,d"), Err("Missing address after ,".into()));
+    assert_eq!(parse("goto"), Err("Missing <line>".into()));
+    assert_eq!(parse("goto x"), Err("<line> should be a number, not x".into()));
+    assert_eq!(parse("goto 1 2"), Err("Too many arguments: 2".into()));
+}
+
+#[test]
+fn substitute_replaces_in_the_range() {
+    let path = temp_file("substitute", "foo foo\nFoo\nfoo\n");
+    let (mut editor, term) = editor(&[path], 40, 6);
+    ctrl(&term, 'p');
+    term.type_str("1,2s/foo/bar/\n");
+    run(&mut editor);
+    assert_eq!(rows(&term, 3), ["bar foo", "Foo", "foo"]);
+    assert_eq!(term.line(5).trim_end(), "1 substitutions on 1 lines");
+    ctrl(&term, 'p');
+    term.type_str("%s/(f)oo/${1}u/gi\n");
+    run(&mut editor);
+    assert_eq!(rows(&term, 3), ["bar fu", "Fu", "fu"]);
+    assert_eq!(term.line(5).trim_end(), "3 substitutions on 3 lines");
+}
+
+#[test]
+fn substitute_takes_other_delimiters_and_vim_groups() {
+    let path = temp_file("substitute-delims", "a/b c\n");
+    let (mut editor, term) = editor(&[path], 40, 5);
+    ctrl(&term, 'p');
+    term.type_str("s#(\\w)/(\\w)#\\2\\#\\1#\n");
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["b#a c"]);
+    ctrl(&term, 'p');
+    term.type_str("s/x/y/\n");
+    run(&mut editor);
+    assert_eq!(term.line(4).trim_end(), "Pattern not found: x");
+}
+
+#[test]
+fn switching_buffers_keeps_the_cursor() {
+    let first = temp_file("switch-a", "first file\nline two\n");
+    let second = temp_file("switch-b", "second\n");
+    let (mut editor, term) = editor(&[first, second], 40, 5);
+    key(&term, KeyCode::Down);
+    key(&term, KeyCode::End);
+    ctrl(&term, 'p');
+    term.type_str("bn\n");
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["second"]);
+    assert_eq!(term.cursor(), Some(Coords { x: 0, y: 0 }));
+    ctrl(&term, 'p');
+    term.type_str("bn\n");
+    run(&mut editor);
+    assert_eq!(rows(&term, 2), ["first file", "line two"]);
+    assert_eq!(term.cursor(), Some(Coords { x: 8, y: 1 }));
+}
@@ -0,0 +1,91 @@
+#![allow(dead_code)]
+
+use std::{cell::RefCell, fs, io, path::PathBuf, rc::Rc};
+use crossterm::event::{KeyCode, KeyModifiers};
+use ded::{
+    editor::{Coords, Editor, clipboard::Provider, config::Config, keymap::Keymap},
+    file::OpenFile,
+    term::{Term, headless::Headless},
+};
+
+/// Collects what a `Term` writes.
+#[derive(Clone, Default)]
+pub struct Sink(Rc<RefCell<Vec<u8>>>);
+
+impl Sink {
+    /// Everything written since the last call.
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.borrow_mut())
+    }
+}
+
+impl io::Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A terminal of `width` by `height` whose output is captured.
+pub fn captured(width: usize, height: usize) -> (Term, Sink) {
+    let sink = Sink::default();
+    (Term::with_output(Coords { x: width, y: height }, sink.clone()), sink)
+}
+
+/// A file in the temp directory holding `text`, unique to the test that asks for it.
+pub fn temp_file(name: &str, text: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ded-{}-{}.txt", name, std::process::id()));
+    fs::write(&path, text).unwrap();
+    path
+}
+
+/// The default options, with the clipboard kept inside the editor.
+pub fn config() -> Config {
+    let mut config = Config::new();
+    config.clipboard = Provider::Internal;
+    config
+}
+
+/// An editor on a headless terminal of `width` by `height`, with `files` open.
+pub fn editor_with_keymap(
+    config: Config, keymap: Keymap, files: &[PathBuf], width: usize, height: usize,
+) -> (Editor, Headless) {
+    let term = Headless::new(Coords { x: width, y: height });
+    let files = files.iter().map(|path| OpenFile::new(path).unwrap()).collect();
+    let editor = Editor::new(Term::with_backend(term.clone()), files, config, keymap).unwrap();
+    (editor, term)
+}
+
+pub fn editor_with(config: Config, files: &[PathBuf], width: usize, height: usize) -> (Editor, Headless) {
+    editor_with_keymap(config, Keymap::new(), files, width, height)
+}
+
+pub fn editor(files: &[PathBuf], width: usize, height: usize) -> (Editor, Headless) {
+    editor_with(config(), files, width, height)
+}
+
+pub fn key(term: &Headless, code: KeyCode) {
+    term.push_key(code, KeyModifiers::NONE);
+}
+
+pub fn ctrl(term: &Headless, c: char) {
+    term.push_key(KeyCode::Char(c), KeyModifiers::CONTROL);
+}
+
+pub fn alt(term: &Headless, c: char) {
+    term.push_key(KeyCode::Char(c), KeyModifiers::ALT);
+}
+
+/// The first `n` rows of the screen, without trailing blanks.
+pub fn rows(term: &Headless, n: usize) -> Vec<String> {
+    (0..n).map(|y| term.line(y).trim_end().to_string()).collect()
+}
+
+/// Handles every queued event, drawing the screen as it goes.
+pub fn run(editor: &mut Editor) {
+    editor.run().unwrap();
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use std::{env, fs, panic, sync::{Mutex, MutexGuard}, thread};
+use ded::crash;
+use common::{ctrl, editor, temp_file};
+
+static ENV: Mutex<()> = Mutex::new(());
+
+/// Points the data directory somewhere of the test's own, returning the recovery
+/// directory in it.
+fn data_home(name: &str) -> (MutexGuard<'static, ()>, std::path::PathBuf) {
+    let guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    let home = env::temp_dir().join(format!("ded-data-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&home);
+    env::set_var("XDG_DATA_HOME", &home);
+    (guard, crash::recovery_dir().unwrap())
+}
+
+fn recovered(dir: &std::path::Path) -> Vec<String> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries.map(|e| fs::read_to_string(e.unwrap().path()).unwrap()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[test]
+fn unsaved_changes_are_recovered_when_input_ends() {
+    let (_env, dir) = data_home("input-ends");
+    let path = temp_file("recover", "hello\n");
+    let (mut editor, term) = editor(&[path], 40, 5);
+    term.type_str("x");
+    crash::run(&mut editor).unwrap();
+    assert_eq!(recovered(&dir), ["xhello\n"]);
+}
+
+#[test]
+fn nothing_is_recovered_after_quitting() {
+    let (_env, dir) = data_home("quit");
+    let path = temp_file("discard", "hello\n");
+    let (mut editor, term) = editor(&[path], 40, 5);
+    term.type_str("x");
+    ctrl(&term, 'p');
+    term.type_str("q!\n");
+    crash::run(&mut editor).unwrap();
+    assert!(editor.has_quit());
+    assert!(recovered(&dir).is_empty());
+}
+
+#[test]
+fn saved_files_need_no_recovery() {
+    let (_env, dir) = data_home("saved");
+    let path = temp_file("saved", "hello\n");
+    let (mut editor, _term) = editor(&[path], 40, 5);
+    crash::run(&mut editor).unwrap();
+    assert!(recovered(&dir).is_empty());
+}
+
+#[test]
+fn panics_off_the_editor_thread_are_only_logged() {
+    let (_env, _) = data_home("thread");
+    let log = crash::log_path().unwrap();
+    crash::install_hook();
+    let result = thread::spawn(|| panic!("background trouble")).join();
+    let _ = panic::take_hook();
+    assert!(result.is_err());
+    assert!(fs::read_to_string(log).unwrap().contains("background trouble"));
+}
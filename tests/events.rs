@@ -0,0 +1,100 @@
+mod common;
+
+use std::{
+    fs, path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use serde_json::json;
+use ded::event::{
+    channel::{Events, Message, Timer},
+    process::{self, Output},
+    watch::Watcher,
+};
+use common::{ctrl, editor, key, rows, run, temp_file};
+
+const WAIT: Duration = Duration::from_secs(5);
+
+fn show_message(kind: u8, text: &str) -> serde_json::Value {
+    json!({ "method": "window/showMessage", "params": { "type": kind, "message": text } })
+}
+
+#[test]
+fn timers_fire_once_due() {
+    let mut events = Events::new();
+    assert!(events.try_next().is_none());
+    events.set_timer(Timer::Message, Instant::now() + Duration::from_secs(60));
+    events.set_timer(Timer::Keys, Instant::now() + Duration::from_millis(10));
+    assert!(matches!(events.next_before(None), Some(Message::Timer(Timer::Keys))));
+    events.cancel_timer(Timer::Message);
+    assert!(events.next_before(Some(Instant::now() + Duration::from_millis(10))).is_none());
+}
+
+#[test]
+fn input_is_taken_ahead_of_other_messages() {
+    let mut events = Events::new();
+    let sender = events.sender();
+    sender.send(Message::FileChanged(PathBuf::from("a"))).unwrap();
+    let key = Event::Key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+    sender.send(Message::Input(key.clone())).unwrap();
+    assert_eq!(events.next_input(Instant::now() + WAIT), Some(key));
+    assert!(matches!(events.try_next(), Some(Message::FileChanged(path)) if path.as_os_str() == "a"));
+    assert_eq!(events.next_input(Instant::now() + Duration::from_millis(10)), None);
+}
+
+#[test]
+fn process_output_comes_a_line_at_a_time() {
+    let (sender, receiver) = mpsc::channel();
+    process::spawn(7, "echo one; echo two >&2; exit 3", sender).unwrap();
+    let mut output = Vec::new();
+    while let Ok(Message::Process(id, line)) = receiver.recv_timeout(WAIT) {
+        assert_eq!(id, 7);
+        output.push(line);
+    }
+    assert_eq!(output.last(), Some(&Output::Exit(Some(3))));
+    assert!(output.contains(&Output::Stdout("one".into())));
+    assert!(output.contains(&Output::Stderr("two".into())));
+}
+
+#[test]
+fn watched_files_report_changes() {
+    let path = temp_file("events-watch", "before");
+    let (sender, receiver) = mpsc::channel();
+    let watcher = Watcher::new(sender);
+    watcher.watch(&path);
+    thread::sleep(Duration::from_millis(20));
+    fs::remove_file(&path).unwrap();
+    match receiver.recv_timeout(WAIT) {
+        Ok(Message::FileChanged(changed)) => assert_eq!(changed, path),
+        other => panic!("expected a change, got {:?}", other),
+    }
+}
+
+#[test]
+fn the_watcher_thread_ends_with_the_watcher() {
+    let (sender, receiver) = mpsc::channel();
+    let watcher = Watcher::new(sender);
+    drop(watcher);
+    assert_eq!(receiver.recv_timeout(WAIT).unwrap_err(), RecvTimeoutError::Disconnected);
+}
+
+#[test]
+fn language_servers_show_messages() {
+    let path = temp_file("events-lsp", "");
+    let (mut editor, term) = editor(&[path], 60, 6);
+    let sender = editor.event_sender();
+    sender.send(Message::Lsp("rls".into(), json!({ "method": "textDocument/publishDiagnostics" }))).unwrap();
+    sender.send(Message::Lsp("rls".into(), show_message(3, "indexing"))).unwrap();
+    sender.send(Message::Lsp("rls".into(), show_message(2, "slow"))).unwrap();
+    sender.send(Message::Lsp("rls".into(), show_message(1, "crashed"))).unwrap();
+    run(&mut editor);
+    assert_eq!(term.line(5).trim_end(), "rls: crashed");
+    ctrl(&term, 'p');
+    term.type_str("messages");
+    key(&term, KeyCode::Enter);
+    run(&mut editor);
+    let logged: Vec<String> = rows(&term, 3).iter().map(|row| row[9..].to_string()).collect();
+    assert_eq!(logged, ["info    rls: indexing", "warning rls: slow", "error   rls: crashed"]);
+}
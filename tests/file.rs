@@ -0,0 +1,110 @@
+use std::{fs, io, path::PathBuf, time::{Duration, SystemTime}};
+use ded::{editor::{cursor::Cursor, selection::{Selection, Selections}}, file::OpenFile};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ded-{}-{}.txt", name, std::process::id()))
+}
+
+fn touch(path: &PathBuf, text: &str) {
+    fs::write(path, text).unwrap();
+    let later = SystemTime::now() + Duration::from_secs(60);
+    fs::File::options().write(true).open(path).unwrap().set_modified(later).unwrap();
+}
+
+#[test]
+fn open_or_new_starts_missing_files_empty() {
+    let path = temp_path("missing");
+    let file = OpenFile::open_or_new(&path).unwrap();
+    assert!(file.is_empty());
+    assert_eq!(file.path(), path);
+    assert!(!path.exists());
+}
+
+#[test]
+fn open_or_new_returns_other_errors() {
+    let dir = std::env::temp_dir().join(format!("ded-dir-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let err = OpenFile::open_or_new(&dir).unwrap_err();
+    assert_ne!(err.kind(), io::ErrorKind::NotFound);
+    assert!(err.to_string().starts_with(&dir.display().to_string()));
+    fs::remove_dir(&dir).unwrap();
+
+    let bytes = temp_path("bytes");
+    fs::write(&bytes, [b'a', 0xff, 0xfe]).unwrap();
+    let err = OpenFile::open_or_new(&bytes).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    fs::remove_file(&bytes).unwrap();
+}
+
+#[test]
+fn save_replaces_the_file_and_leaves_no_temp_file() {
+    let path = temp_path("save");
+    fs::write(&path, "old\n").unwrap();
+    let mut file = OpenFile::new(&path).unwrap();
+    file.insert(0, "new ");
+    assert!(file.is_dirty());
+    file.save().unwrap();
+    assert!(!file.is_dirty());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new old\n");
+    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+    let leftovers = fs::read_dir(std::env::temp_dir()).unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let other = entry.file_name().to_string_lossy().into_owned();
+            other.contains(&name) && other != name
+        })
+        .count();
+    assert_eq!(leftovers, 0);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn save_refuses_to_overwrite_changes_on_disk() {
+    let path = temp_path("changed");
+    fs::write(&path, "mine\n").unwrap();
+    let mut file = OpenFile::new(&path).unwrap();
+    assert!(!file.changed_on_disk());
+    touch(&path, "theirs\n");
+    assert!(file.changed_on_disk());
+    file.insert(0, "edited ");
+    assert!(file.save().is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "theirs\n");
+    file.force_save().unwrap();
+    assert!(!file.changed_on_disk());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "edited mine\n");
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn save_as_moves_the_file_and_keeps_existing_ones() {
+    let (from, to) = (temp_path("save-as-from"), temp_path("save-as-to"));
+    fs::write(&from, "text\n").unwrap();
+    fs::write(&to, "keep\n").unwrap();
+    let mut file = OpenFile::new(&from).unwrap();
+    assert_eq!(file.save_as(&to, false).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+    assert_eq!(file.path(), from);
+    file.save_as(&to, true).unwrap();
+    assert_eq!(file.path(), to);
+    assert_eq!(fs::read_to_string(&to).unwrap(), "text\n");
+    fs::remove_file(&from).unwrap();
+    fs::remove_file(&to).unwrap();
+}
+
+#[test]
+fn stashed_selections_come_back_inside_the_file() {
+    let path = temp_path("selections");
+    fs::write(&path, "one\ntwo\n").unwrap();
+    let mut file = OpenFile::new(&path).unwrap();
+    let mut selections = Selections::default();
+    selections.set(Selection::point(Cursor::new(1, 2)));
+    file.stash_selections(selections);
+    assert_eq!(file.take_selections().primary().head.coords(), Cursor::new(1, 2).coords());
+
+    let mut selections = Selections::default();
+    selections.set(Selection::point(Cursor::new(9, 9)));
+    file.stash_selections(selections);
+    let head = file.take_selections().primary().head.clone();
+    assert!(head.line < file.len());
+    assert!(head.col <= file.get(head.line).unwrap().len());
+    fs::remove_file(&path).unwrap();
+}
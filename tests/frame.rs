@@ -0,0 +1,72 @@
+mod common;
+
+use crossterm::style::{Attribute, Color};
+use ded::{editor::Coords, term::frame::{Frame, Style}};
+use common::captured;
+
+fn frame(lines: &[&str]) -> Frame {
+    let mut frame = Frame::new(Coords { x: 6, y: lines.len() });
+    for (y, line) in lines.iter().enumerate() {
+        frame.print(0, y, line, Style::default());
+    }
+    frame
+}
+
+#[test]
+fn printing_cuts_at_the_edge_and_spans_wide_graphemes() {
+    let mut frame = Frame::new(Coords { x: 6, y: 2 });
+    assert_eq!(frame.print(0, 0, "ab界cdef", Style::default()), 6);
+    assert_eq!(frame.line(0), "ab界cd");
+    assert_eq!(frame.get(2, 0).unwrap().width, 2);
+    assert_eq!(frame.get(3, 0).unwrap().width, 0);
+    // A wide grapheme that doesn't fit leaves the last column blank.
+    assert_eq!(frame.print(0, 1, "abcde界", Style::default()), 6);
+    assert_eq!(frame.line(1), "abcde ");
+}
+
+#[test]
+fn overwriting_half_a_wide_grapheme_blanks_the_other_half() {
+    let mut first = frame(&["a界b"]);
+    first.print(1, 0, "x", Style::default());
+    assert_eq!(first.line(0), "ax b  ");
+    let mut second = frame(&["a界b"]);
+    second.print(2, 0, "y", Style::default());
+    assert_eq!(second.line(0), "a yb  ");
+}
+
+#[test]
+fn diff_holds_only_changed_cells() {
+    let prev = frame(&["hello", "world"]);
+    assert!(frame(&["hello", "world"]).diff(&prev).is_empty());
+
+    let next = frame(&["hello", "wxrld"]);
+    let changed: Vec<_> = next.diff(&prev).into_iter().map(|(at, cell)| (at, cell.symbol.clone())).collect();
+    assert_eq!(changed, [(Coords { x: 1, y: 1 }, "x".to_string())]);
+
+    let mut styled = frame(&["hello", "world"]);
+    styled.print(0, 0, "h", Style::default().fg(Color::Red).attr(Attribute::Bold));
+    assert_eq!(styled.diff(&prev).len(), 1);
+
+    let resized = frame(&["hello"]);
+    assert_eq!(resized.diff(&prev).len(), 6);
+}
+
+#[test]
+fn drawing_writes_only_what_changed() {
+    let (mut term, sink) = captured(6, 2);
+    term.draw(&frame(&["hello", "world"])).unwrap();
+    let first = String::from_utf8(sink.take()).unwrap();
+    assert!(first.contains("hello") && first.contains("world"), "{:?}", first);
+
+    term.draw(&frame(&["hello", "world"])).unwrap();
+    let same = String::from_utf8(sink.take()).unwrap();
+    assert!(!same.contains('h') && !same.contains('w'), "{:?}", same);
+
+    term.draw(&frame(&["hello", "wxrld"])).unwrap();
+    let changed = String::from_utf8(sink.take()).unwrap();
+    assert!(changed.contains('x') && !changed.contains("rld"), "{:?}", changed);
+
+    term.invalidate();
+    term.draw(&frame(&["hello", "wxrld"])).unwrap();
+    assert!(String::from_utf8(sink.take()).unwrap().contains("hello"));
+}
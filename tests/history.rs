@@ -0,0 +1,91 @@
+use std::{fs, io};
+use ded::{buffer::Buffer, file::history::{Edit, History}};
+
+type Tamper = fn(&mut serde_json::Value);
+
+fn saved(name: &str, buffer: &mut Buffer) -> (std::path::PathBuf, serde_json::Value) {
+    let mut history = History::new();
+    for (at, text) in [(0, "a"), (1, "b")] {
+        let edit = Edit::Insert { at, text: text.into() };
+        edit.apply(buffer);
+        history.record(edit);
+        history.seal();
+    }
+    let path = std::env::temp_dir().join(format!("ded-history-{}-{}", name, std::process::id()));
+    history.save(&path, buffer).unwrap();
+    let json = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    (path, json)
+}
+
+fn load_tampered(name: &str, tamper: Tamper) -> io::Result<History> {
+    let mut buffer = Buffer::new();
+    let (path, mut json) = saved(name, &mut buffer);
+    tamper(&mut json);
+    fs::write(&path, json.to_string()).unwrap();
+    let loaded = History::load(&path, &buffer);
+    fs::remove_file(&path).unwrap();
+    loaded
+}
+
+#[test]
+fn loads_what_it_saved() {
+    let loaded = load_tampered("valid", |_| {}).unwrap();
+    assert_eq!(loaded.len(), 3);
+    assert_eq!(loaded.current(), 2);
+}
+
+#[test]
+fn rejects_out_of_range_indices() {
+    let cases: [(&str, Tamper); 3] = [
+        ("current", |json| json["history"]["current"] = 7.into()),
+        ("parent", |json| json["history"]["revisions"][2]["parent"] = 9.into()),
+        ("child", |json| json["history"]["revisions"][0]["last_child"] = 5.into()),
+    ];
+    for (name, tamper) in cases {
+        let err = load_tampered(name, tamper).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", name);
+    }
+}
+
+#[test]
+fn rejects_cycles() {
+    let err = load_tampered("cycle", |json| json["history"]["revisions"][1]["parent"] = 2.into()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn rejects_edits_outside_the_text() {
+    let cases: [(&str, Tamper); 3] = [
+        ("insert-past-end", |json| json["history"]["revisions"][2]["edits"][0]["Insert"]["at"] = 5.into()),
+        ("longer-than-buffer", |json| json["history"]["revisions"][1]["edits"][0]["Insert"]["text"] = "abc".into()),
+        ("delete-overflow", |json| {
+            json["history"]["revisions"][2]["edits"][0] = serde_json::json!({"Delete": {"at": u64::MAX, "text": "b"}});
+        }),
+    ];
+    for (name, tamper) in cases {
+        let err = load_tampered(name, tamper).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", name);
+    }
+}
+
+#[test]
+fn save_replaces_the_old_file() {
+    let mut buffer = Buffer::new();
+    let (path, _) = saved("replace", &mut buffer);
+    let mut history = History::load(&path, &buffer).unwrap();
+    let edit = Edit::Insert { at: 2, text: "c".into() };
+    edit.apply(&mut buffer);
+    history.record(edit);
+    history.save(&path, &buffer).unwrap();
+    assert_eq!(History::load(&path, &buffer).unwrap().len(), 4);
+    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+    let leftovers = fs::read_dir(path.parent().unwrap()).unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let other = entry.file_name().to_string_lossy().into_owned();
+            other.contains(&name) && other != name
+        })
+        .count();
+    assert_eq!(leftovers, 0);
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use std::time::Duration;
+use crossterm::event::{KeyCode, KeyModifiers};
+use ded::{
+    editor::{Coords, Editor, action::Action, keymap::{KeyChord, Keymap, Lookup}},
+    term::headless::Headless,
+};
+use common::{config, ctrl, editor_with_keymap, rows, run, temp_file};
+
+fn chord(s: &str) -> KeyChord {
+    s.parse().unwrap()
+}
+
+/// What the keys in `keys` run, or how they resolve if they don't make a binding.
+fn lookup(keymap: &Keymap, keys: &str, mode: &str, filetype: &str) -> String {
+    let keys: Vec<KeyChord> = keys.split(' ').map(chord).collect();
+    match keymap.lookup(&keys, mode, filetype) {
+        Lookup::Found(actions) => format!("{:?}", actions.actions()),
+        other => format!("{:?}", other),
+    }
+}
+
+#[test]
+fn chords_parse_and_print() {
+    assert_eq!(chord("ctrl+shift+z"), KeyChord::new(KeyCode::Char('Z'), KeyModifiers::CONTROL));
+    assert_eq!(chord("shift+a"), chord("A"));
+    assert_eq!(chord("C+M+f5"), KeyChord::new(KeyCode::F(5), KeyModifiers::CONTROL | KeyModifiers::ALT));
+    assert_eq!(chord("ctrl++"), KeyChord::new(KeyCode::Char('+'), KeyModifiers::CONTROL));
+    assert_eq!(chord("alt+space").to_string(), "alt+space");
+    assert_eq!(chord("ctrl+pageup").to_string(), "ctrl+pageup");
+    assert_eq!("hyper+x".parse::<KeyChord>(), Err("unknown modifier: hyper".into()));
+    assert_eq!("ctrl+nope".parse::<KeyChord>(), Err("unknown key: nope".into()));
+}
+
+#[test]
+fn unbound_chars_are_typed() {
+    let keymap = Keymap::new();
+    assert_eq!(lookup(&keymap, "x", "insert", "text"), "Unbound");
+    assert!(matches!(Keymap::fallback(chord("x"), "insert"), Action::Input(c) if c == "x"));
+    assert!(matches!(Keymap::fallback(chord("ctrl+g"), "insert"), Action::Unregistered(_)));
+}
+
+#[test]
+fn the_most_specific_layer_wins() {
+    let mut keymap = Keymap::new();
+    let errors = keymap.load(r#"
+        [global]
+        "ctrl+b" = ":goto 1"
+        [insert]
+        "ctrl+b" = ":goto 2"
+        [filetype.rust]
+        "ctrl+b" = ":goto 3"
+        [filetype.rust.insert]
+        "ctrl+b" = ":goto 4"
+        [filetype.toml.normal]
+        "ctrl+q" = "none"
+    "#);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(lookup(&keymap, "ctrl+b", "normal", "text"), r#"[Command("goto 1")]"#);
+    assert_eq!(lookup(&keymap, "ctrl+b", "insert", "text"), r#"[Command("goto 2")]"#);
+    assert_eq!(lookup(&keymap, "ctrl+b", "normal", "rust"), r#"[Command("goto 3")]"#);
+    assert_eq!(lookup(&keymap, "ctrl+b", "insert", "rust"), r#"[Command("goto 4")]"#);
+    assert_eq!(lookup(&keymap, "ctrl+q", "insert", "toml"), "[Quit]");
+    assert_eq!(lookup(&keymap, "ctrl+q", "normal", "toml"), "Unbound");
+}
+
+#[test]
+fn bad_bindings_are_skipped_and_reported() {
+    let mut keymap = Keymap::new();
+    let errors = keymap.load(r#"
+        [global]
+        "ctrl+b" = "fly away"
+        "hyper+x" = "quit"
+        "ctrl+e" = 3
+        "ctrl+g" = "quit"
+        modes = "oops"
+    "#);
+    assert_eq!(errors.len(), 4, "{:?}", errors);
+    assert_eq!(lookup(&keymap, "ctrl+g", "insert", "text"), "[Quit]");
+    assert_eq!(keymap.load("[global").len(), 1);
+}
+
+#[test]
+fn bound_commands_run_in_the_editor() {
+    let path = temp_file("keymap-goto", "one\ntwo\nthree\n");
+    let mut keymap = Keymap::new();
+    assert!(keymap.load("[global]\n\"ctrl+b\" = \":goto 3\"\n\"x\" = \"move down\"").is_empty());
+    let (mut editor, term) = editor_with_keymap(config(), keymap, &[path], 40, 6);
+    term.push_key(KeyCode::Char('b'), KeyModifiers::CONTROL);
+    term.type_str("ax");
+    run(&mut editor);
+    assert_eq!(rows(&term, 4), ["one", "two", "athree", ""]);
+}
+
+/// An editor on "one\ntwo\nthree\n" with `bindings` loaded and a short key timeout.
+fn sequence_editor(name: &str, bindings: &str) -> (Editor, Headless) {
+    let path = temp_file(name, "one\ntwo\nthree\n");
+    let mut keymap = Keymap::new();
+    assert!(keymap.load(bindings).is_empty());
+    let mut config = config();
+    config.key_timeout = Duration::from_millis(10);
+    editor_with_keymap(config, keymap, &[path], 40, 6)
+}
+
+#[test]
+fn a_sequence_runs_once_complete() {
+    let (mut editor, term) = sequence_editor("keys-complete", "[insert]\n\"j k\" = \":goto 3\"");
+    term.type_str("jk");
+    run(&mut editor);
+    assert_eq!(rows(&term, 3), ["one", "two", "three"]);
+    assert_eq!(term.cursor(), Some(Coords { x: 0, y: 2 }));
+    assert!(editor.pending_keys().is_empty());
+}
+
+#[test]
+fn keys_that_break_a_sequence_are_typed() {
+    let (mut editor, term) = sequence_editor("keys-unbound", "[insert]\n\"j k\" = \":goto 3\"");
+    term.type_str("jx");
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["jxone"]);
+    term.type_str("jjk");
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["jxjone"]);
+    assert_eq!(term.cursor(), Some(Coords { x: 0, y: 2 }));
+}
+
+#[test]
+fn keys_left_waiting_are_typed_when_they_time_out() {
+    let (mut editor, term) = sequence_editor("keys-timeout", "[insert]\n\"j k\" = \":goto 3\"");
+    term.type_str("j");
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["one"]);
+    assert_eq!(editor.pending_keys().len(), 1);
+    term.pause();
+    term.type_str("a");
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["jaone"]);
+}
+
+#[test]
+fn a_bound_prefix_runs_when_nothing_completes_it() {
+    let bindings = "[global]\n\"ctrl+g\" = \"move down\"\n\"ctrl+g ctrl+g\" = \":goto 3\"";
+    let (mut editor, term) = sequence_editor("keys-prefix", bindings);
+    ctrl(&term, 'g');
+    term.pause();
+    run(&mut editor);
+    assert_eq!(term.cursor(), Some(Coords { x: 0, y: 1 }));
+    ctrl(&term, 'g');
+    term.type_str("x");
+    run(&mut editor);
+    assert_eq!(rows(&term, 3), ["one", "two", "xthree"]);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use std::path::Path;
+use crossterm::event::KeyCode;
+use ded::{
+    editor::{Editor, action::Action, macros::{self, Macros}},
+    term::headless::Headless,
+};
+use common::{config, editor, editor_with, key, rows, run, temp_file};
+
+fn modal(name: &str, text: &str) -> (Editor, Headless) {
+    let path = temp_file(name, text);
+    let mut config = config();
+    config.modal = true;
+    editor_with(config, &[path], 40, 6)
+}
+
+fn keys(editor: &mut Editor, term: &Headless, keys: &str) {
+    term.type_str(keys);
+    run(editor);
+}
+
+fn input(text: &str) -> Action {
+    Action::Input(text.to_string())
+}
+
+#[test]
+fn typed_text_is_recorded_as_one_action() {
+    let mut macros = Macros::new();
+    macros.record(&input("a"));
+    macros.start('q');
+    for action in [input("a"), input("b"), input("\n"), input("c"), Action::Undo].iter() {
+        macros.record(action);
+    }
+    let (register, actions) = macros.stop().unwrap();
+    assert_eq!(register, 'q');
+    assert_eq!(macros::to_text(&actions), "input ab\ninput \\n\ninput c\nundo\n");
+    assert_eq!(macros.recording(), None);
+}
+
+#[test]
+fn replayed_actions_are_not_recorded_again() {
+    let mut macros = Macros::new();
+    macros.start('a');
+    macros.enter().unwrap();
+    macros.record(&input("x"));
+    macros.leave();
+    macros.record(&input("y"));
+    assert_eq!(macros::to_text(&macros.stop().unwrap().1), "input y\n");
+}
+
+#[test]
+fn nesting_is_limited() {
+    let mut macros = Macros::new();
+    let nested = (0..1000).take_while(|_| macros.enter().is_ok()).count();
+    assert!(nested > 0 && nested < 1000);
+}
+
+#[test]
+fn macro_text_parses_line_by_line() {
+    let actions = macros::parse("# a comment\nmove down\n\n  undo\n").unwrap();
+    assert_eq!(macros::to_text(&actions), "move down 1\nundo\n");
+    assert_eq!(macros::parse("undo\nfly away").unwrap_err(), "line 2: unknown action: fly");
+}
+
+#[test]
+fn macro_buffers_name_their_register() {
+    assert_eq!(macros::buffer_register(Path::new(&macros::buffer_name('a'))), Some('a'));
+    assert_eq!(macros::buffer_register(Path::new("[macro ab]")), None);
+    assert_eq!(macros::buffer_register(Path::new("notes.txt")), None);
+}
+
+#[test]
+fn function_keys_record_and_replay() {
+    let path = temp_file("macro-keys", "");
+    let (mut editor, term) = editor(&[path], 40, 6);
+    key(&term, KeyCode::F(3));
+    run(&mut editor);
+    assert_eq!(editor.recording(), Some('q'));
+    term.type_str("ab");
+    key(&term, KeyCode::F(3));
+    key(&term, KeyCode::F(4));
+    key(&term, KeyCode::F(4));
+    run(&mut editor);
+    assert_eq!(editor.recording(), None);
+    assert_eq!(rows(&term, 1), ["ababab"]);
+}
+
+#[test]
+fn a_register_replays_with_a_count() {
+    let (mut editor, term) = modal("macro-count", "one\ntwo\nthree\nfour");
+    keys(&mut editor, &term, "qaA;");
+    key(&term, KeyCode::Esc);
+    keys(&mut editor, &term, "jq");
+    assert_eq!(rows(&term, 4), ["one;", "two", "three", "four"]);
+    keys(&mut editor, &term, "2@a");
+    assert_eq!(rows(&term, 4), ["one;", "two;", "three;", "four"]);
+    keys(&mut editor, &term, "@@");
+    assert_eq!(rows(&term, 4), ["one;", "two;", "three;", "four;"]);
+}
+
+#[test]
+fn visual_lines_replay_once_each() {
+    let (mut editor, term) = modal("macro-visual", "one\ntwo\nthree");
+    keys(&mut editor, &term, "qbI-");
+    key(&term, KeyCode::Esc);
+    keys(&mut editor, &term, "qjVj@b");
+    assert_eq!(rows(&term, 3), ["-one", "-two", "-three"]);
+}
+
+#[test]
+fn replay_command_runs_over_a_range() {
+    let (mut editor, term) = modal("macro-range", "one\ntwo\nthree");
+    keys(&mut editor, &term, "qcxq");
+    keys(&mut editor, &term, ":2,3replay c\n");
+    assert_eq!(rows(&term, 3), ["ne", "wo", "hree"]);
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::time::Duration;
+use crossterm::event::{KeyCode, KeyModifiers};
+use ded::editor::{clipboard::Provider, message::{Level, Messages}};
+use common::{config, ctrl, editor_with, key, run, temp_file};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[test]
+fn the_latest_message_is_shown_until_dismissed() {
+    let mut messages = Messages::new();
+    assert!(messages.current(TIMEOUT).is_none());
+    messages.info("saved");
+    messages.error("failed");
+    let shown = messages.current(TIMEOUT).unwrap();
+    assert_eq!((shown.level, shown.text.as_str()), (Level::Error, "failed"));
+    messages.dismiss();
+    assert!(messages.current(TIMEOUT).is_none());
+    assert_eq!(messages.history().len(), 2);
+}
+
+#[test]
+fn messages_time_out() {
+    let mut messages = Messages::new();
+    messages.warn("soon gone");
+    assert!(messages.time_left(TIMEOUT).is_some());
+    assert!(messages.current(Duration::from_millis(0)).is_none());
+    assert!(messages.time_left(Duration::from_millis(0)).is_none());
+}
+
+#[test]
+fn the_history_lists_every_message_with_its_level() {
+    let mut messages = Messages::new();
+    messages.info("one");
+    messages.error("two\nlines");
+    let text = messages.to_text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with(" info    one"), "{}", lines[0]);
+    assert!(lines[1].ends_with(" error   two"), "{}", lines[1]);
+    assert_eq!(lines[2], format!("{:17}lines", ""));
+}
+
+#[test]
+fn errors_show_in_the_bottom_line_until_escape() {
+    let path = temp_file("message-error", "text\n");
+    let mut config = config();
+    config.clipboard = Provider::command("false", "false");
+    config.clipboard_sync = true;
+    config.message_timeout = TIMEOUT;
+    let (mut editor, term) = editor_with(config, &[path], 40, 5);
+    term.push_key(KeyCode::Right, KeyModifiers::SHIFT);
+    ctrl(&term, 'c');
+    run(&mut editor);
+    let bottom = term.line(4);
+    assert!(bottom.starts_with("Could not copy to the clipboard: "), "{}", bottom);
+    key(&term, KeyCode::Esc);
+    run(&mut editor);
+    assert_eq!(term.line(4).trim_end(), "");
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use crossterm::event::KeyCode;
+use ded::{
+    editor::{Coords, Editor, config::Config, mode::Mode},
+    term::headless::Headless,
+};
+use common::{config, editor, editor_with, key, rows, run, temp_file};
+
+fn modal(name: &str, text: &str) -> (Editor, Headless) {
+    let path = temp_file(name, text);
+    let mut config = config();
+    config.modal = true;
+    editor_with(config, &[path], 40, 6)
+}
+
+fn keys(editor: &mut Editor, term: &Headless, keys: &str) {
+    term.type_str(keys);
+    run(editor);
+}
+
+#[test]
+fn editing_is_modeless_by_default() {
+    assert!(!Config::new().modal);
+    let path = temp_file("mode-default", "one");
+    let (mut editor, term) = editor(&[path], 40, 6);
+    keys(&mut editor, &term, "x");
+    assert_eq!(editor.mode(), Mode::Insert);
+    assert_eq!(rows(&term, 1), ["xone"]);
+}
+
+#[test]
+fn the_cursor_stays_on_the_last_char() {
+    let (mut editor, term) = modal("mode-end", "abc\ndef");
+    keys(&mut editor, &term, "$");
+    assert_eq!(term.cursor(), Some(Coords { x: 2, y: 0 }));
+    keys(&mut editor, &term, "x");
+    assert_eq!(rows(&term, 2), ["ab", "def"]);
+    assert_eq!(term.cursor(), Some(Coords { x: 1, y: 0 }));
+}
+
+#[test]
+fn sideways_motion_stays_on_the_line() {
+    let (mut editor, term) = modal("mode-sideways", "ab\ncd");
+    keys(&mut editor, &term, "lll");
+    assert_eq!(term.cursor(), Some(Coords { x: 1, y: 0 }));
+    keys(&mut editor, &term, "jhhh");
+    assert_eq!(term.cursor(), Some(Coords { x: 0, y: 1 }));
+}
+
+#[test]
+fn deleting_chars_stays_on_the_line() {
+    let (mut editor, term) = modal("mode-x", "ab\ncd\nefgh");
+    keys(&mut editor, &term, "$xxx");
+    assert_eq!(rows(&term, 3), ["", "cd", "efgh"]);
+    keys(&mut editor, &term, "jXX");
+    assert_eq!(rows(&term, 3), ["", "cd", "efgh"]);
+    keys(&mut editor, &term, "j3x");
+    assert_eq!(rows(&term, 3), ["", "cd", "h"]);
+    keys(&mut editor, &term, "k$dl");
+    assert_eq!(rows(&term, 3), ["", "c", "h"]);
+}
+
+#[test]
+fn visual_mode_keeps_the_cursor_on_the_line() {
+    let (mut editor, term) = modal("mode-visual", "ab\ncd");
+    keys(&mut editor, &term, "vllld");
+    assert_eq!(rows(&term, 2), ["", "cd"]);
+}
+
+#[test]
+fn appending_inserts_after_the_cursor() {
+    let (mut editor, term) = modal("mode-append", "ab\n\ncd");
+    keys(&mut editor, &term, "a!");
+    key(&term, KeyCode::Esc);
+    keys(&mut editor, &term, "$a?");
+    key(&term, KeyCode::Esc);
+    assert_eq!(rows(&term, 3), ["a!b?", "", "cd"]);
+    keys(&mut editor, &term, "ja.");
+    key(&term, KeyCode::Esc);
+    keys(&mut editor, &term, "jA;");
+    key(&term, KeyCode::Esc);
+    run(&mut editor);
+    assert_eq!(rows(&term, 3), ["a!b?", ".", "cd;"]);
+    assert_eq!(editor.mode(), Mode::Normal);
+}
@@ -0,0 +1,52 @@
+use ded::{buffer::Buffer, editor::{action::{Location, Target}, motion}};
+
+fn to(text: &str, location: Location, head: usize) -> Option<usize> {
+    motion::to(&Buffer::from(text), &location, head)
+}
+
+#[test]
+fn home_toggles_between_indent_and_line_start() {
+    let text = "x\n    let y";
+    assert_eq!(to(text, Location::Beginning(Target::Line), 9), Some(6));
+    assert_eq!(to(text, Location::Beginning(Target::Line), 6), Some(2));
+    assert_eq!(to(text, Location::End(Target::Line), 3), Some(11));
+    assert_eq!(to(text, Location::End(Target::Line), 0), Some(1));
+}
+
+#[test]
+fn words_stop_at_class_changes() {
+    let text = "foo  bar.baz";
+    assert_eq!(to(text, Location::End(Target::Word), 0), Some(3));
+    assert_eq!(to(text, Location::End(Target::Word), 3), Some(8));
+    assert_eq!(to(text, Location::End(Target::Word), 8), Some(9));
+    assert_eq!(to(text, Location::Beginning(Target::Word), 8), Some(5));
+    assert_eq!(to(text, Location::Beginning(Target::Word), 9), Some(8));
+    assert_eq!(to(text, Location::Beginning(Target::Word), 5), Some(0));
+}
+
+#[test]
+fn paragraphs_are_separated_by_blank_lines() {
+    let text = "a\nb\n\nc\nd";
+    assert_eq!(to(text, Location::End(Target::Paragraph), 0), Some(3));
+    assert_eq!(to(text, Location::End(Target::Paragraph), 3), Some(8));
+    assert_eq!(to(text, Location::Beginning(Target::Paragraph), 8), Some(5));
+    assert_eq!(to(text, Location::Beginning(Target::Paragraph), 5), Some(0));
+}
+
+#[test]
+fn chars_are_found_on_the_current_line_only() {
+    let text = "axbxc\nx";
+    assert_eq!(to(text, Location::End(Target::Char('x')), 0), Some(1));
+    assert_eq!(to(text, Location::End(Target::Char('x')), 1), Some(3));
+    assert_eq!(to(text, Location::End(Target::Char('x')), 3), None);
+    assert_eq!(to(text, Location::Beginning(Target::Char('x')), 4), Some(3));
+    assert_eq!(to(text, Location::Beginning(Target::Char('a')), 0), None);
+}
+
+#[test]
+fn indices_and_documents() {
+    let text = "héllo\nworld";
+    assert_eq!(to(text, Location::Index(3), 8), Some(9));
+    assert_eq!(to(text, Location::Beginning(Target::Document), 8), Some(0));
+    assert_eq!(to(text, Location::End(Target::Document), 0), Some(11));
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use std::fs;
+use crossterm::event::{Event, KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ded::{
+    editor::{Coords, Editor},
+    event::mouse::{Clicks, Unit},
+    term::headless::Headless,
+};
+use common::{config, editor, editor_with, key, rows, run, temp_file};
+
+fn mouse(term: &Headless, kind: MouseEventKind, column: u16, row: u16) {
+    term.push_event(Event::Mouse(MouseEvent { kind, column, row, modifiers: KeyModifiers::NONE }));
+}
+
+fn click(term: &Headless, x: u16, y: u16) {
+    mouse(term, MouseEventKind::Down(MouseButton::Left), x, y);
+    mouse(term, MouseEventKind::Up(MouseButton::Left), x, y);
+}
+
+fn drag(term: &Headless, from: (u16, u16), to: (u16, u16)) {
+    mouse(term, MouseEventKind::Down(MouseButton::Left), from.0, from.1);
+    mouse(term, MouseEventKind::Drag(MouseButton::Left), to.0, to.1);
+    mouse(term, MouseEventKind::Up(MouseButton::Left), to.0, to.1);
+}
+
+fn numbered(name: &str, text: &str) -> (Editor, Headless) {
+    let path = temp_file(name, text);
+    let mut config = config();
+    config.line_numbers = true;
+    editor_with(config, &[path], 20, 6)
+}
+
+#[test]
+fn clicks_in_a_row_select_more() {
+    let mut clicks = Clicks::new();
+    assert_eq!(clicks.press((1, 1)), Unit::Char);
+    assert_eq!(clicks.press((1, 1)), Unit::Word);
+    assert_eq!(clicks.press((1, 1)), Unit::Line);
+    assert_eq!(clicks.press((1, 1)), Unit::Char);
+    assert_eq!(clicks.press((2, 1)), Unit::Char);
+}
+
+#[test]
+fn a_click_places_the_cursor() {
+    let path = temp_file("mouse-click", "hello world\nsecond");
+    let (mut editor, term) = editor(&[path], 40, 6);
+    click(&term, 3, 1);
+    run(&mut editor);
+    assert_eq!(term.cursor(), Some(Coords { x: 3, y: 1 }));
+    click(&term, 30, 3);
+    run(&mut editor);
+    assert_eq!(term.cursor(), Some(Coords { x: 6, y: 1 }));
+}
+
+#[test]
+fn a_double_click_selects_a_word() {
+    let path = temp_file("mouse-word", "hello world");
+    let (mut editor, term) = editor(&[path], 40, 6);
+    click(&term, 8, 0);
+    click(&term, 8, 0);
+    term.type_str("X");
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["hello X"]);
+}
+
+#[test]
+fn dragging_selects() {
+    let path = temp_file("mouse-drag", "hello world\nsecond");
+    let (mut editor, term) = editor(&[path], 40, 6);
+    drag(&term, (2, 0), (3, 1));
+    term.type_str("Y");
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["heYond"]);
+}
+
+#[test]
+fn clicks_skip_the_gutter() {
+    let (mut editor, term) = numbered("mouse-gutter", "hello\nworld");
+    click(&term, 6, 1);
+    term.type_str("Z");
+    run(&mut editor);
+    assert_eq!(rows(&term, 2), ["  1 hello", "  2 woZrld"]);
+    click(&term, 1, 0);
+    term.type_str("line\n");
+    run(&mut editor);
+    assert_eq!(rows(&term, 2), ["  1 line", "  2 woZrld"]);
+}
+
+#[test]
+fn clicks_follow_the_view_across() {
+    let (mut editor, term) = numbered("mouse-offset", &"0123456789".repeat(4));
+    key(&term, KeyCode::End);
+    run(&mut editor);
+    let shown = term.line(0)[4..].trim_end().to_string();
+    assert!(!"0123456789".repeat(4).starts_with(&shown));
+    click(&term, 4, 0);
+    term.type_str("!");
+    run(&mut editor);
+    assert_eq!(term.line(0)[4..].trim_end(), format!("!{}", shown));
+}
+
+#[test]
+fn the_wheel_scrolls_the_view() {
+    let text: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+    let path = temp_file("mouse-wheel", &text);
+    let (mut editor, term) = editor(&[path], 40, 6);
+    mouse(&term, MouseEventKind::ScrollDown, 0, 0);
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["line 4"]);
+    assert_eq!(term.cursor(), Some(Coords { x: 0, y: 0 }));
+    mouse(&term, MouseEventKind::ScrollUp, 0, 0);
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["line 1"]);
+}
+
+#[test]
+fn clicking_the_dirty_marker_does_not_write() {
+    let path = temp_file("mouse-dirty", "saved");
+    let (mut editor, term) = editor(std::slice::from_ref(&path), 100, 6);
+    term.type_str("edit ");
+    run(&mut editor);
+    let status = (0..6).map(|y| term.line(y)).position(|line| line.contains("[+]")).unwrap();
+    let x = term.line(status).find("[+]").unwrap();
+    click(&term, x as u16 + 1, status as u16);
+    run(&mut editor);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "saved");
+    assert!(term.line(status).contains("[+]"));
+}
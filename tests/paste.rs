@@ -0,0 +1,64 @@
+mod common;
+
+use std::fs;
+use crossterm::event::KeyCode;
+use ded::editor::Coords;
+use common::{config, ctrl, editor, editor_with, key, rows, run, temp_file};
+
+#[test]
+fn a_paste_is_one_insertion() {
+    let path = temp_file("paste-undo", "end");
+    let (mut editor, term) = editor(&[path], 40, 6);
+    term.type_str("typed ");
+    term.paste("one\ntwo ");
+    run(&mut editor);
+    assert_eq!(rows(&term, 2), ["typed one", "two end"]);
+    ctrl(&term, 'z');
+    run(&mut editor);
+    assert_eq!(rows(&term, 2), ["typed end", "~"]);
+}
+
+#[test]
+fn pasted_text_runs_no_bindings() {
+    let path = temp_file("paste-normal", "text");
+    let mut config = config();
+    config.modal = true;
+    let (mut editor, term) = editor_with(config, &[path], 40, 6);
+    term.paste("dd:q\n");
+    run(&mut editor);
+    assert_eq!(rows(&term, 2), ["dd:q", "text"]);
+}
+
+#[test]
+fn pasted_line_breaks_follow_the_file() {
+    let path = temp_file("paste-crlf", "one\r\ntwo\r\n");
+    let (mut editor, term) = editor(std::slice::from_ref(&path), 40, 6);
+    term.paste("a\rb\n");
+    ctrl(&term, 's');
+    run(&mut editor);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a\r\nb\r\none\r\ntwo\r\n");
+}
+
+#[test]
+fn a_prompt_takes_the_first_line() {
+    let path = temp_file("paste-prompt", "one\ntwo\nthree");
+    let (mut editor, term) = editor(&[path], 40, 6);
+    ctrl(&term, 'p');
+    term.paste("goto 3\nquit\n");
+    key(&term, KeyCode::Enter);
+    run(&mut editor);
+    assert_eq!(term.cursor(), Some(Coords { x: 0, y: 2 }));
+    assert_eq!(rows(&term, 3), ["one", "two", "three"]);
+}
+
+#[test]
+fn a_paste_is_recorded_in_a_macro() {
+    let path = temp_file("paste-macro", "");
+    let (mut editor, term) = editor(&[path], 40, 6);
+    key(&term, KeyCode::F(3));
+    term.paste("ab");
+    key(&term, KeyCode::F(3));
+    key(&term, KeyCode::F(4));
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["abab"]);
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use crossterm::event::KeyCode;
+use ded::{
+    editor::{Coords, action::{Action, Direction, Location, Target}},
+    prompt::{Prompt, PromptEvent, PromptHistory, PromptKind},
+};
+use common::{ctrl, editor, key, rows, run, temp_file};
+
+fn input(prompt: &mut Prompt, text: &str) {
+    for c in text.chars() {
+        prompt.handle(&Action::Input(c.to_string()), &[]);
+    }
+}
+
+#[test]
+fn typing_and_editing_the_input() {
+    let mut prompt = Prompt::new(PromptKind::Command);
+    input(&mut prompt, "wq all");
+    prompt.handle(&Action::Delete(Direction::To(Location::Beginning(Target::Word))), &[]);
+    assert_eq!(prompt.input(), "wq ");
+    prompt.handle(&Action::Move(Direction::To(Location::Beginning(Target::Line))), &[]);
+    input(&mut prompt, "x");
+    prompt.handle(&Action::Delete(Direction::Right(1)), &[]);
+    assert_eq!(prompt.input(), "xq ");
+    assert_eq!(prompt.handle(&Action::Newline(Direction::Right(1)), &[]), PromptEvent::Submit("xq ".into()));
+}
+
+#[test]
+fn backspace_on_an_empty_prompt_cancels_it() {
+    let mut prompt = Prompt::new(PromptKind::Search);
+    input(&mut prompt, "a");
+    assert_eq!(prompt.handle(&Action::Delete(Direction::Left(1)), &[]), PromptEvent::Pending);
+    assert_eq!(prompt.handle(&Action::Delete(Direction::Left(1)), &[]), PromptEvent::Cancel);
+}
+
+#[test]
+fn up_and_down_walk_the_history_and_back_to_the_draft() {
+    let mut history = PromptHistory::new();
+    history.push(PromptKind::Search, "one");
+    history.push(PromptKind::Search, "two");
+    history.push(PromptKind::Search, "one");
+    let entries = history.get(PromptKind::Search).to_vec();
+    assert_eq!(entries, ["two", "one"]);
+    assert!(history.get(PromptKind::Command).is_empty());
+
+    let mut prompt = Prompt::new(PromptKind::Search);
+    input(&mut prompt, "dr");
+    prompt.handle(&Action::Move(Direction::Up(1)), &entries);
+    assert_eq!(prompt.input(), "one");
+    prompt.handle(&Action::Move(Direction::Up(1)), &entries);
+    assert_eq!(prompt.input(), "two");
+    prompt.handle(&Action::Move(Direction::Down(1)), &entries);
+    prompt.handle(&Action::Move(Direction::Down(1)), &entries);
+    assert_eq!(prompt.input(), "dr");
+}
+
+#[test]
+fn tab_cycles_through_completions() {
+    let mut prompt = Prompt::new(PromptKind::Path)
+        .with_completer(|input| vec![format!("{}a", input), format!("{}b", input)]);
+    input(&mut prompt, "x");
+    input(&mut prompt, "\t");
+    assert_eq!(prompt.input(), "xa");
+    input(&mut prompt, "\t");
+    assert_eq!(prompt.input(), "xb");
+    input(&mut prompt, "\t");
+    assert_eq!(prompt.input(), "xa");
+}
+
+#[test]
+fn confirmations_take_a_single_answer() {
+    let mut prompt = Prompt::confirm("Quit?");
+    assert_eq!(prompt.label(), "Quit? (y/n) ");
+    assert_eq!(prompt.handle(&Action::Input("x".into()), &[]), PromptEvent::Pending);
+    assert_eq!(prompt.handle(&Action::Input("Y".into()), &[]), PromptEvent::Submit("y".into()));
+    assert_eq!(prompt.handle(&Action::Input("n".into()), &[]), PromptEvent::Cancel);
+}
+
+#[test]
+fn long_input_scrolls_to_keep_the_cursor_visible() {
+    let prompt = Prompt::new(PromptKind::Command).with_input("abcdefghij");
+    assert_eq!(prompt.render(8), ("defghij".to_string(), 7));
+    let prompt = Prompt::new(PromptKind::Command).with_input("abc");
+    assert_eq!(prompt.render(8), (":abc".to_string(), 4));
+}
+
+#[test]
+fn searching_selects_the_next_match() {
+    let path = temp_file("prompt-search", "one two one\n");
+    let (mut editor, term) = editor(&[path], 40, 4);
+    ctrl(&term, 'f');
+    term.type_str("one");
+    run(&mut editor);
+    assert_eq!(term.line(3).trim_end(), "/one");
+    assert_eq!(term.cursor(), Some(Coords { x: 4, y: 3 }));
+    key(&term, KeyCode::Enter);
+    run(&mut editor);
+    assert_eq!(term.cursor(), Some(Coords { x: 3, y: 0 }));
+    ctrl(&term, 'f');
+    term.type_str("one\n");
+    run(&mut editor);
+    assert_eq!(term.cursor(), Some(Coords { x: 11, y: 0 }));
+    ctrl(&term, 'f');
+    term.type_str("three\n");
+    run(&mut editor);
+    assert_eq!(term.line(3).trim_end(), "Pattern not found: three");
+}
+
+#[test]
+fn opening_a_path_switches_to_it() {
+    let first = temp_file("prompt-open-a", "first\n");
+    let second = temp_file("prompt-open-b", "second\n");
+    let (mut editor, term) = editor(std::slice::from_ref(&first), 60, 5);
+    key(&term, KeyCode::End);
+    ctrl(&term, 'o');
+    term.type_str(&format!("{}\n", second.display()));
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["second"]);
+    ctrl(&term, 'o');
+    term.type_str(&format!("{}\n", first.display()));
+    run(&mut editor);
+    assert_eq!(rows(&term, 1), ["first"]);
+    assert_eq!(term.cursor(), Some(Coords { x: 5, y: 0 }));
+}
+
+#[test]
+fn unknown_commands_are_reported() {
+    let (mut editor, term) = editor(&[], 40, 4);
+    ctrl(&term, 'p');
+    term.type_str("frobnicate\n");
+    run(&mut editor);
+    assert_eq!(term.line(3).trim_end(), "Not an editor command: frobnicate");
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use crossterm::event::{KeyCode, KeyModifiers};
+use ded::{editor::register::{Registers, Yank}, term::headless::Headless};
+use common::{alt, ctrl, editor, key, rows, run, temp_file};
+
+fn yank(text: &str) -> Yank {
+    Yank::new(vec![text.to_string()], false)
+}
+
+#[test]
+fn copies_and_deletes_fill_the_unnamed_register() {
+    let mut registers = Registers::new();
+    registers.yank(yank("copied"));
+    assert_eq!(registers.paste(), Some(yank("copied")));
+    registers.kill(yank("deleted"));
+    assert_eq!(registers.paste(), Some(yank("deleted")));
+    assert_eq!(registers.get(Registers::LAST_YANK), Some(&yank("copied")));
+}
+
+#[test]
+fn named_registers_are_written_only_when_selected() {
+    let mut registers = Registers::new();
+    registers.select('a');
+    registers.yank(yank("one"));
+    registers.yank(yank("two"));
+    registers.select('A');
+    registers.kill(Yank::new(vec!["three".into()], true));
+    let a = registers.get('a').unwrap();
+    assert_eq!(a.texts, ["one", "three"]);
+    assert!(a.linewise);
+    registers.select('a');
+    assert_eq!(registers.paste().unwrap().texts, ["one", "three"]);
+    assert_eq!(registers.paste(), Some(Yank::new(vec!["three".into()], true)));
+    assert_eq!(registers.names(), ['"', '0', 'a']);
+}
+
+#[test]
+fn cycling_walks_back_through_the_kill_ring() {
+    let mut registers = Registers::new();
+    assert_eq!(registers.cycle(), None);
+    for text in ["first", "second", "third"] {
+        registers.kill(yank(text));
+    }
+    assert_eq!(registers.paste(), Some(yank("third")));
+    assert_eq!(registers.cycle(), Some(yank("second")));
+    assert_eq!(registers.cycle(), Some(yank("first")));
+    assert_eq!(registers.cycle(), Some(yank("third")));
+    registers.paste();
+    assert_eq!(registers.cycle(), Some(yank("second")));
+}
+
+/// Copies the line under the cursor, leaving the cursor at the start of the next one.
+fn copy_line(term: &Headless) {
+    term.push_key(KeyCode::Down, KeyModifiers::SHIFT);
+    ctrl(term, 'c');
+}
+
+#[test]
+fn cycling_a_linewise_paste_at_the_end_of_the_file() {
+    let path = temp_file("cycle-eof", "xyz\nabc");
+    let (mut editor, term) = editor(&[path], 40, 6);
+    copy_line(&term);
+    key(&term, KeyCode::Esc);
+    ctrl(&term, 'v');
+    alt(&term, 'y');
+    run(&mut editor);
+    assert_eq!(rows(&term, 4), ["xyz", "abc", "xyz", "~"]);
+}
+
+#[test]
+fn cycling_through_the_kill_ring_at_the_end_of_the_file() {
+    let path = temp_file("cycle-ring", "xyz\nabc\nend");
+    let (mut editor, term) = editor(&[path], 40, 8);
+    copy_line(&term);
+    key(&term, KeyCode::Esc);
+    copy_line(&term);
+    key(&term, KeyCode::Esc);
+    ctrl(&term, 'v');
+    run(&mut editor);
+    assert_eq!(rows(&term, 5), ["xyz", "abc", "end", "abc", "~"]);
+    alt(&term, 'y');
+    run(&mut editor);
+    assert_eq!(rows(&term, 5), ["xyz", "abc", "end", "xyz", "~"]);
+    alt(&term, 'y');
+    run(&mut editor);
+    assert_eq!(rows(&term, 5), ["xyz", "abc", "end", "abc", "~"]);
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use crossterm::event::{Event, KeyCode};
+use ded::{editor::Coords, term::headless::Headless};
+use common::{ctrl, editor, key, rows, run, temp_file};
+
+fn resize(term: &Headless, width: u16, height: u16) {
+    term.push_event(Event::Resize(width, height));
+}
+
+fn numbered_lines(n: usize) -> String {
+    (1..=n).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n")
+}
+
+#[test]
+fn the_screen_is_redrawn_at_the_new_size() {
+    let path = temp_file("resize-shrink", "a line longer than ten\nsecond");
+    let (mut editor, term) = editor(&[path], 40, 8);
+    run(&mut editor);
+    resize(&term, 10, 4);
+    run(&mut editor);
+    assert_eq!(*term.frame().dims(), Coords { x: 10, y: 4 });
+    assert_eq!(rows(&term, 2), ["a line lon", "second"]);
+    resize(&term, 40, 8);
+    run(&mut editor);
+    assert_eq!(rows(&term, 3), ["a line longer than ten", "second", "~"]);
+}
+
+#[test]
+fn growing_pulls_the_view_back_to_the_text() {
+    let path = temp_file("resize-grow", &numbered_lines(20));
+    let (mut editor, term) = editor(&[path], 40, 6);
+    ctrl(&term, 'p');
+    term.type_str("goto 20");
+    key(&term, KeyCode::Enter);
+    run(&mut editor);
+    assert_eq!(rows(&term, 4).last().unwrap(), "line 20");
+    resize(&term, 40, 12);
+    run(&mut editor);
+    assert_eq!(rows(&term, 10), (11..=20).map(|n| format!("line {}", n)).collect::<Vec<_>>());
+    assert_eq!(term.cursor(), Some(Coords { x: 0, y: 9 }));
+}
+
+#[test]
+fn the_cursor_stays_in_view() {
+    let path = temp_file("resize-narrow", &"0123456789".repeat(3));
+    let (mut editor, term) = editor(&[path], 40, 6);
+    key(&term, KeyCode::End);
+    run(&mut editor);
+    assert_eq!(term.cursor(), Some(Coords { x: 30, y: 0 }));
+    resize(&term, 12, 6);
+    run(&mut editor);
+    let cursor = term.cursor().unwrap();
+    assert!(cursor.x < 12);
+    assert!(rows(&term, 1)[0].ends_with("789"));
+}
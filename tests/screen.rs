@@ -0,0 +1,41 @@
+mod common;
+
+use crossterm::event::KeyCode;
+use ded::editor::Coords;
+use common::{editor, key, rows, run, temp_file};
+
+#[test]
+fn typing_and_end_redraw_the_line() {
+    let path = temp_file("typing", "hello\nworld\n");
+    let (mut editor, term) = editor(&[path], 40, 6);
+    term.type_str("ab");
+    key(&term, KeyCode::End);
+    run(&mut editor);
+    assert_eq!(rows(&term, 4), ["abhello", "world", "", "~"]);
+    assert_eq!(term.cursor(), Some(Coords { x: 7, y: 0 }));
+}
+
+#[test]
+fn an_empty_buffer_shows_the_welcome_line() {
+    let (mut editor, term) = editor(&[], 40, 8);
+    run(&mut editor);
+    let welcome = term.line(2);
+    assert!(welcome.starts_with('~'), "{}", welcome);
+    assert!(welcome.contains(&format!("Div {}", env!("CARGO_PKG_VERSION"))), "{}", welcome);
+    assert_eq!(term.cursor(), Some(Coords { x: 0, y: 0 }));
+}
+
+#[test]
+fn moving_past_the_last_row_scrolls() {
+    let path = temp_file("scroll", "1\n2\n3\n4\n5\n");
+    let (mut editor, term) = editor(&[path], 20, 5);
+    run(&mut editor);
+    assert_eq!(rows(&term, 3), ["1", "2", "3"]);
+    for _ in 0..3 {
+        key(&term, KeyCode::Down);
+    }
+    run(&mut editor);
+    assert_eq!(rows(&term, 3), ["2", "3", "4"]);
+    assert_eq!(term.cursor(), Some(Coords { x: 0, y: 2 }));
+}
+
@@ -0,0 +1,51 @@
+mod common;
+
+use ded::{editor::status::Segment, file::OpenFile};
+use common::{config, editor, editor_with, rows, run, temp_file};
+
+#[test]
+fn the_default_status_line_shows_the_file_and_cursor() {
+    let path = temp_file("status", "hello\nworld\n");
+    let name = path.display().to_string();
+    let (mut editor, term) = editor(&[path], 100, 5);
+    term.type_str("ab");
+    run(&mut editor);
+    let status = term.line(3);
+    assert!(status.starts_with(&format!(" INSERT  {}  [+]", name)), "{}", status);
+    assert!(status.trim_end().ends_with("text  utf-8  LF  1:3  3 lines  [1/1]"), "{}", status);
+    assert_eq!(status.chars().count(), 100);
+}
+
+#[test]
+fn segments_follow_the_config() {
+    let path = temp_file("status-config", "a\r\nb\r\n");
+    let mut config = config();
+    config.status = vec![Segment::Position, Segment::Spacer, Segment::LineEnding];
+    let (mut editor, term) = editor_with(config, &[path], 20, 4);
+    run(&mut editor);
+    assert_eq!(rows(&term, 3)[2], " 1:1           CRLF");
+}
+
+#[test]
+fn the_left_side_gives_way_when_both_do_not_fit() {
+    let mut config = config();
+    config.status = vec![Segment::Mode, Segment::Path, Segment::Spacer, Segment::Position];
+    let (mut editor, term) = editor_with(config, &[], 12, 4);
+    run(&mut editor);
+    assert_eq!(term.line(2), " INSERT 1:1 ");
+}
+
+#[test]
+fn segments_parse_from_their_names() {
+    assert_eq!("pos".parse(), Ok(Segment::Position));
+    assert_eq!(" eol ".parse(), Ok(Segment::LineEnding));
+    assert_eq!("|".parse(), Ok(Segment::Spacer));
+    assert_eq!("clock".parse::<Segment>(), Err("unknown status segment: clock".to_string()));
+}
+
+#[test]
+fn filetypes_go_by_extension_or_name() {
+    assert_eq!(OpenFile::with_path("src/main.rs").filetype(), "rust");
+    assert_eq!(OpenFile::with_path("Makefile").filetype(), "make");
+    assert_eq!(OpenFile::with_path("notes").filetype(), "text");
+}
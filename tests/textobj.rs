@@ -0,0 +1,53 @@
+use ded::{buffer::Buffer, editor::{action::Target, textobj}};
+
+/// The text `target` covers with the cursor on the first occurrence of `on`.
+fn object(text: &str, on: &str, target: Target) -> Option<String> {
+    let buffer = Buffer::from(text);
+    let at = text[..text.find(on).unwrap()].chars().count();
+    textobj::resolve(&buffer, &target, at..at).map(|range| buffer.slice(range).to_string())
+}
+
+fn around(target: Target) -> Target {
+    Target::Around(Box::new(target))
+}
+
+#[test]
+fn words_take_trailing_blanks_around_them() {
+    assert_eq!(object("foo bar  baz", "bar", Target::Word).as_deref(), Some("bar"));
+    assert_eq!(object("foo bar  baz", "bar", around(Target::Word)).as_deref(), Some("bar  "));
+    assert_eq!(object("foo bar  baz", "baz", around(Target::Word)).as_deref(), Some("  baz"));
+    assert_eq!(object("a.b", ".", Target::Word).as_deref(), Some("."));
+}
+
+#[test]
+fn lines_trim_blanks_inside_and_take_the_newline_around() {
+    assert_eq!(object("  hi there \nnext", "hi", Target::Line).as_deref(), Some("hi there"));
+    assert_eq!(object("  hi there \nnext", "hi", around(Target::Line)).as_deref(), Some("  hi there \n"));
+    assert_eq!(object("first\nlast", "last", around(Target::Line)).as_deref(), Some("last"));
+}
+
+#[test]
+fn brackets_find_the_innermost_enclosing_pair() {
+    let text = "f(a, [b, (c)], d)";
+    assert_eq!(object(text, "b", Target::Bracketed).as_deref(), Some("b, (c)"));
+    assert_eq!(object(text, "b", Target::Parenthesis).as_deref(), Some("a, [b, (c)], d"));
+    assert_eq!(object(text, "c", around(Target::Parenthesis)).as_deref(), Some("(c)"));
+    assert_eq!(object(text, "[", Target::Char(']')).as_deref(), Some("b, (c)"));
+    assert_eq!(object("no brackets", "b", Target::Bracketed), None);
+}
+
+#[test]
+fn quotes_skip_escapes_and_look_ahead_on_the_line() {
+    let text = r#"say "a \"b\" c" and 'x'"#;
+    assert_eq!(object(text, "a ", Target::DoubleQuotes).as_deref(), Some(r#"a \"b\" c"#));
+    assert_eq!(object(text, "say", around(Target::DoubleQuotes)).as_deref(), Some(r#""a \"b\" c""#));
+    assert_eq!(object(text, "and", Target::SingleQuotes).as_deref(), Some("x"));
+    assert_eq!(object(text, "and", Target::Backticks), None);
+}
+
+#[test]
+fn regions_cover_whole_lines() {
+    assert_eq!(object("one\ntwo\nthree", "one", Target::Region(1, 1)).as_deref(), Some("two\n"));
+    assert_eq!(object("one\ntwo\nthree", "one", Target::Region(1, 9)).as_deref(), Some("two\nthree"));
+    assert_eq!(object("one two", "one", Target::Document).as_deref(), Some("one two"));
+}